@@ -2,30 +2,154 @@
 
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::fs::{File, create_dir};
+use std::fs::{self, File, create_dir_all};
+use std::env::current_dir;
+use std::collections::{BTreeMap, HashMap};
 
 use Error;
-use serde_json;
+use serde::Serialize;
+use serde_json::{self, Value};
+use serde_json::ser::PrettyFormatter;
+use toml;
+use ron_format;
+use variables::{self, Variables, VariableError};
+use migration;
+use validation::{self, Diagnostic, DiagnosticBuilder, FilePosition, Severity};
 
+/// Directory in the library directory. Every `.json`, `.toml` and `.ron` file
+/// in the directory is included to the library in file name order.
+pub const LIBRARY_D_DIRECTORY_NAME: &str = "library.d";
 
-#[derive(Deserialize)]
+
+/// File format of a library file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LibraryFormat {
+    Json,
+    Toml,
+    Ron,
+}
+
+impl LibraryFormat {
+    pub const ALL: &'static [LibraryFormat] = &[LibraryFormat::Json, LibraryFormat::Toml, LibraryFormat::Ron];
+
+    /// Format from file extension.
+    pub fn from_path(file_path: &Path) -> Option<LibraryFormat> {
+        let extension = file_path.extension()?;
+
+        LibraryFormat::ALL.iter().cloned().find(|format| extension == format.extension())
+    }
+
+    /// Format from name. Name is the file extension, for example `toml`.
+    pub fn from_name(name: &str) -> Option<LibraryFormat> {
+        LibraryFormat::ALL.iter().cloned().find(|format| format.extension() == name)
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            LibraryFormat::Json => "json",
+            LibraryFormat::Toml => "toml",
+            LibraryFormat::Ron => "ron",
+        }
+    }
+
+    /// File name of the library file in the library directory, for example `library.toml`.
+    pub fn library_file_name(self) -> String {
+        format!("library.{}", self.extension())
+    }
+}
+
+/// Library file with an include list.
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct LibraryFile {
+    /// Version of the library file format, see `migration::LIBRARY_VERSION`.
+    pub version: u32,
+    /// Library files or directories of library files to include. Relative
+    /// paths are relative to the directory of the file which includes them.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub programs: Vec<LibraryFileProgram>,
+    /// Unknown fields. These are kept when the library file is saved.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+/// Program as it is in the library file. Variables are not substituted.
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq)]
 pub struct LibraryFileProgram {
     pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub download_command: Option<CommandData>,
     pub working_directory: String,
-    pub command_queues: Vec<CommandQueue>
+    pub command_queues: Vec<CommandQueue>,
+    /// Environment variables for every command of the program.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub env: BTreeMap<String, String>,
+    /// Environment variables to remove for every command of the program.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub env_remove: Vec<String>,
+    /// Start every command of the program with an empty environment.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub clear_env: bool,
+    /// User defined variables for variable substitution.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub variables: BTreeMap<String, String>,
+    /// Unknown fields. These are kept when the library file is saved.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq)]
 pub struct CommandQueue {
     pub name: String,
     pub commands: Vec<CommandData>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub failure_policy: FailurePolicy,
+    /// Unknown fields. These are kept when the library file is saved.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+/// What to do with the rest of the `CommandQueue` when
+/// a command exits with non-zero status or fails to start.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FailurePolicy {
+    /// Clear the command queue. This is the default.
+    #[default]
+    Abort,
+    /// Run the next command from the queue.
+    Continue,
+    /// Run the failed command again at most N times
+    /// and clear the command queue if it still fails.
+    Retry(u32),
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq)]
 pub struct CommandData {
     pub executable: String,
     pub args: Vec<String>,
+    /// Environment variables to set. Overrides program's environment variables.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub env: BTreeMap<String, String>,
+    /// Environment variables to remove.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub env_remove: Vec<String>,
+    /// Start the command with an empty environment. Environment variables
+    /// of the program are not set.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub clear_env: bool,
+    /// Working directory relative to the program's working directory. It
+    /// must be inside the program's working directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    /// Unknown fields. These are kept when the library file is saved.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
 }
 
 
@@ -34,51 +158,89 @@ pub struct Program {
     pub download_command: Option<CommandData>,
     /// Absolute path
     pub working_directory: PathBuf,
-    pub command_queues: Vec<CommandQueue>
+    pub command_queues: Vec<CommandQueue>,
+    pub env: BTreeMap<String, String>,
+    pub env_remove: Vec<String>,
+    pub clear_env: bool,
+    /// Library file where the program is defined.
+    pub source: PathBuf,
 }
 
 pub struct ProgramLibrary {
+    /// Programs of the library file first and then programs of the included files.
     pub programs: Vec<Program>,
+    /// Warnings found when loading the library, or errors if the library could not be loaded.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Library file for editing. None if the library has errors.
+    pub(crate) library_file: Option<LibraryFile>,
+    /// Library files and directories which were read when loading the library.
+    pub(crate) source_files: Vec<PathBuf>,
+}
+
+impl ProgramLibrary {
+    /// Library without programs. Used when the library file has errors.
+    pub fn empty(diagnostics: Vec<Diagnostic>) -> ProgramLibrary {
+        ProgramLibrary {
+            programs: vec![],
+            diagnostics,
+            library_file: None,
+            source_files: vec![],
+        }
+    }
+
+    /// Programs of the library file without variable substitution. Programs of
+    /// included files are not part of the library file.
+    ///
+    /// Returns None if the library has errors. Library can't be edited then.
+    pub fn library_file_programs(&self) -> Option<&[LibraryFileProgram]> {
+        self.library_file.as_ref().map(|library_file| library_file.programs.as_slice())
+    }
 }
 
 
 
 pub const DEFAULT_LIBRARY_FILE: &'static str = r#"
 
-[
-    {
-        "name": "Space Boss Battles",
-        "download_command": {
-            "executable": "git",
-            "args": ["clone", "https://github.com/jutuon/space-boss-battles", "space_boss_battles"]
-        },
-        "working_directory": "space_boss_battles",
-        "command_queues" : [
-            {
-                "name": "Run",
-                "commands": [
-                    {
-                        "executable" : "cargo",
-                        "args": ["run", "--release"]
-                    }
-                ]
+{
+    "version": 1,
+    "programs": [
+        {
+            "name": "Space Boss Battles",
+            "variables": {
+                "directory": "space_boss_battles"
             },
-            {
-                "name": "Update and build",
-                "commands": [
-                    {
-                        "executable" : "git",
-                        "args": ["pull"]
-                    },
-                    {
-                        "executable" : "cargo",
-                        "args": ["build", "--release"]
-                    }
-                ]
-            }
-        ]
-    }
-]
+            "download_command": {
+                "executable": "git",
+                "args": ["clone", "https://github.com/jutuon/space-boss-battles", "${directory}"]
+            },
+            "working_directory": "${directory}",
+            "command_queues" : [
+                {
+                    "name": "Run",
+                    "commands": [
+                        {
+                            "executable" : "cargo",
+                            "args": ["run", "--release"]
+                        }
+                    ]
+                },
+                {
+                    "name": "Update and build",
+                    "commands": [
+                        {
+                            "executable" : "git",
+                            "args": ["pull"]
+                        },
+                        {
+                            "executable" : "cargo",
+                            "args": ["build", "--release"]
+                        }
+                    ]
+                }
+            ]
+        }
+    ]
+}
 
 
 "#;
@@ -87,13 +249,16 @@ pub(crate) fn create_library_directory_if_not_exists(directory_path: &Path) -> R
     if directory_path.exists() {
         Ok(())
     } else {
-        match create_dir(directory_path) {
+        match create_dir_all(directory_path) {
             Ok(_) => Ok(()),
             Err(io_error) => Err(Error::IoError(io_error)),
         }
     }
 }
 
+/// Loads the library. Format of the library file is selected by the file extension.
+///
+/// Library files of older versions are upgraded, see `migration::migrate`.
 pub(crate) fn load_library(file_path: &Path, library_directory: &Path) -> Result<ProgramLibrary, Error> {
     let mut file = match File::open(file_path) {
         Ok(file) => file,
@@ -107,26 +272,455 @@ pub(crate) fn load_library(file_path: &Path, library_directory: &Path) -> Result
         Err(io_error) => return Err(Error::IoError(io_error)),
     }
 
-    let library_file_programs: Vec<LibraryFileProgram> = match serde_json::from_str(&text) {
-        Ok(library) => library,
-        Err(parse_error) => return Err(Error::ParseError(parse_error)),
+    let mut migration_diagnostics = vec![];
+
+    let text = match migration::migrate(file_path, text, &mut migration_diagnostics) {
+        Some(text) => text,
+        None => return Err(Error::Validation(migration_diagnostics)),
+    };
+
+    match parse_library(file_path, &text, library_directory) {
+        Ok(mut program_library) => {
+            migration_diagnostics.extend(program_library.diagnostics);
+            program_library.diagnostics = migration_diagnostics;
+            Ok(program_library)
+        }
+        Err(Error::Validation(diagnostics)) => {
+            migration_diagnostics.extend(diagnostics);
+            Err(Error::Validation(migration_diagnostics))
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Library file as text. Unknown fields are not kept in RON library files.
+pub(crate) fn to_text(format: LibraryFormat, library_file: &LibraryFile) -> Result<String, Error> {
+    let mut text = match format {
+        LibraryFormat::Json => {
+            let mut bytes = vec![];
+
+            {
+                let formatter = PrettyFormatter::with_indent(b"    ");
+                let mut serializer = serde_json::Serializer::with_formatter(&mut bytes, formatter);
+
+                if let Err(error) = library_file.serialize(&mut serializer) {
+                    return Err(Error::Serialize(error.to_string()));
+                }
+            }
+
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+        LibraryFormat::Toml => {
+            match toml::to_string_pretty(library_file) {
+                Ok(text) => text,
+                Err(error) => return Err(Error::Serialize(error.to_string())),
+            }
+        }
+        LibraryFormat::Ron => {
+            match ron_format::to_string(library_file) {
+                Ok(text) => text,
+                Err(error) => return Err(Error::Serialize(error.to_string())),
+            }
+        }
+    };
+
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+
+    Ok(text)
+}
+
+/// Writes the library file in the format of the file extension. Library is
+/// validated before writing.
+pub(crate) fn save_library(file_path: &Path, library_file: &LibraryFile, library_directory: &Path) -> Result<ProgramLibrary, Error> {
+    let format = LibraryFormat::from_path(file_path).unwrap_or(LibraryFormat::Json);
+    let text = to_text(format, library_file)?;
+    let program_library = parse_library(file_path, &text, library_directory)?;
+
+    write_file(file_path, &text)?;
+
+    Ok(program_library)
+}
+
+/// Writes text to a temporary file which is then renamed to `file_path`,
+/// so the file is always complete.
+pub(crate) fn write_file(file_path: &Path, text: &str) -> Result<(), Error> {
+    let mut temp_file_path = file_path.as_os_str().to_os_string();
+    temp_file_path.push(".tmp");
+    let temp_file_path = PathBuf::from(temp_file_path);
+
+    let result = File::create(&temp_file_path)
+        .and_then(|mut file| {
+            file.write_all(text.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_file_path, file_path));
+
+    if let Err(io_error) = result {
+        let _ = fs::remove_file(&temp_file_path);
+        return Err(Error::IoError(io_error));
+    }
+
+    Ok(())
+}
+
+/// Library file which programs are merged to the library.
+struct SourceFile {
+    path: PathBuf,
+    text: String,
+    library_file: LibraryFile,
+}
+
+/// Parses library file in the format of the file extension. Library file
+/// should be upgraded to the current version before parsing.
+fn parse_library_file(file_path: &Path, text: &str, diagnostics: &mut Vec<Diagnostic>) -> Option<LibraryFile> {
+    let diagnostic = match LibraryFormat::from_path(file_path).unwrap_or(LibraryFormat::Json) {
+        LibraryFormat::Json => {
+            match serde_json::from_str(text) {
+                Ok(library_file) => return Some(library_file),
+                Err(parse_error) => DiagnosticBuilder::parse_error(file_path, &parse_error),
+            }
+        }
+        LibraryFormat::Toml => {
+            match toml::from_str(text) {
+                Ok(library_file) => return Some(library_file),
+                Err(parse_error) => {
+                    let position = parse_error.span().map(|span| validation::file_position(text, span.start));
+                    DiagnosticBuilder::format_error(file_path, position, parse_error.message().to_string())
+                }
+            }
+        }
+        LibraryFormat::Ron => {
+            match ron_format::from_str(text) {
+                Ok(library_file) => return Some(library_file),
+                Err(parse_error) => {
+                    let position = FilePosition {
+                        line: parse_error.position.line,
+                        column: parse_error.position.col,
+                    };
+
+                    DiagnosticBuilder::format_error(file_path, Some(position), parse_error.code.to_string())
+                }
+            }
+        }
+    };
+
+    diagnostics.push(diagnostic);
+
+    None
+}
+
+fn read_source_file(file_path: &Path, diagnostics: &mut Vec<Diagnostic>) -> Option<SourceFile> {
+    let text = match fs::read_to_string(file_path) {
+        Ok(text) => text,
+        Err(io_error) => {
+            diagnostics.push(validation::file_error(file_path, io_error.to_string()));
+            return None;
+        }
     };
 
-    let programs = library_file_programs.into_iter().map(|item| {
-        let mut working_directory = library_directory.to_path_buf();
-        // push replaces current path if argument is absolute path
-        working_directory.push(item.working_directory);
+    let text = migration::upgrade_included(file_path, text, diagnostics)?;
 
-        Program {
-            name: item.name,
-            download_command: item.download_command,
-            working_directory,
-            command_queues: item.command_queues,
+    parse_library_file(file_path, &text, diagnostics).map(|library_file| {
+        SourceFile {
+            path: file_path.to_path_buf(),
+            text,
+            library_file,
         }
-    }).collect();
+    })
+}
+
+/// Returns library files of the directory in file name order.
+fn library_files_in_directory(directory: &Path, diagnostics: &mut Vec<Diagnostic>) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(io_error) => {
+            diagnostics.push(validation::file_error(directory, io_error.to_string()));
+            return vec![];
+        }
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && LibraryFormat::from_path(path).is_some())
+        .collect();
+
+    files.sort();
+
+    files
+}
+
+/// Reads included library files. Every file is read only once, so include cycles are allowed.
+fn read_included_files(mut sources: Vec<SourceFile>, library_directory: &Path, diagnostics: &mut Vec<Diagnostic>) -> (Vec<SourceFile>, Vec<PathBuf>) {
+    let library_d_directory = library_directory.join(LIBRARY_D_DIRECTORY_NAME);
+
+    let mut source_files = vec![sources[0].path.clone(), library_d_directory.clone()];
+    let mut read_files: Vec<PathBuf> = vec![canonical(&sources[0].path)];
+    let mut source_i = 0;
+
+    while source_i < sources.len() {
+        let mut files = vec![];
+
+        {
+            let source = &sources[source_i];
+            let builder = source.diagnostic_builder();
+
+            let source_directory = source.path.parent().unwrap_or(library_directory);
+
+            for (include_i, include) in source.library_file.include.iter().enumerate() {
+                let path = source_directory.join(include);
+
+                if path.is_dir() {
+                    files.extend(library_files_in_directory(&path, diagnostics));
+                    source_files.push(path);
+                } else if path.is_file() {
+                    files.push(path);
+                } else {
+                    let message = format!("included file `{}` is not found", path.display());
+                    diagnostics.push(builder.include_error(include_i, message));
+                }
+            }
+        }
+
+        if source_i == 0 && library_d_directory.is_dir() {
+            files.extend(library_files_in_directory(&library_d_directory, diagnostics));
+        }
+
+        for file in files {
+            let canonical_file = canonical(&file);
+
+            if read_files.contains(&canonical_file) {
+                continue;
+            }
+
+            read_files.push(canonical_file);
+            source_files.push(file.clone());
+
+            if let Some(source) = read_source_file(&file, diagnostics) {
+                sources.push(source);
+            }
+        }
+
+        source_i += 1;
+    }
+
+    (sources, source_files)
+}
+
+impl SourceFile {
+    /// File positions are only available for JSON files.
+    fn diagnostic_builder<'a>(&'a self) -> DiagnosticBuilder<'a> {
+        match LibraryFormat::from_path(&self.path) {
+            Some(LibraryFormat::Toml) | Some(LibraryFormat::Ron) => DiagnosticBuilder::without_positions(&self.path),
+            Some(LibraryFormat::Json) | None => DiagnosticBuilder::new(&self.path, &self.text),
+        }
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Parses and validates library file text and reads the included library files.
+/// Argument `file_path` is used in diagnostics and for finding the included files.
+fn parse_library(file_path: &Path, text: &str, library_directory: &Path) -> Result<ProgramLibrary, Error> {
+    let mut diagnostics = vec![];
+
+    let library_file = match parse_library_file(file_path, text, &mut diagnostics) {
+        Some(library_file) => library_file,
+        None => return Err(Error::Validation(diagnostics)),
+    };
+
+    let library_directory = if library_directory.is_absolute() {
+        library_directory.to_path_buf()
+    } else {
+        match current_dir() {
+            Ok(current_dir) => current_dir.join(library_directory),
+            Err(io_error) => return Err(Error::IoError(io_error)),
+        }
+    };
+
+    let library_source = SourceFile {
+        path: file_path.to_path_buf(),
+        text: text.to_string(),
+        library_file,
+    };
+
+    let (sources, source_files) = read_included_files(vec![library_source], &library_directory, &mut diagnostics);
+
+    let builders: Vec<DiagnosticBuilder> = sources.iter().map(SourceFile::diagnostic_builder).collect();
+
+    // Program name and (source index, program index) of the first definition.
+    let mut names: HashMap<&str, (usize, usize)> = HashMap::new();
+    let mut programs_by_source = vec![];
+
+    for (source_i, (source, builder)) in sources.iter().zip(&builders).enumerate() {
+        let library_file_programs = &source.library_file.programs;
+        diagnostics.extend(validation::check_library_file_programs(library_file_programs, builder));
+
+        let mut programs = vec![];
+
+        for (i, item) in library_file_programs.iter().enumerate() {
+            match names.get(item.name.as_str()) {
+                Some(&(first_source_i, first_i)) if first_source_i != source_i => {
+                    let first = builders[first_source_i].program_field(Severity::Error, first_i, &item.name, "name", String::new());
+
+                    let message = match first.position {
+                        Some(position) => format!("program name is also defined in `{}` at line {}", first.file.display(), position.line),
+                        None => format!("program name is also defined in `{}`", first.file.display()),
+                    };
+
+                    diagnostics.push(builder.program_field(Severity::Error, i, &item.name, "name", message));
+                }
+                Some(_) => (),
+                None => {
+                    names.insert(&item.name, (source_i, i));
+                }
+            }
+
+            match resolve_program(item, &library_directory, &source.path) {
+                Ok(program) => programs.push(program),
+                Err(variable_error) => diagnostics.push(builder.variable_error(i, &variable_error)),
+            }
+        }
+
+        programs_by_source.push(programs);
+    }
+
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        return Err(Error::Validation(diagnostics));
+    }
+
+    for (programs, builder) in programs_by_source.iter().zip(&builders) {
+        diagnostics.extend(validation::check_programs(programs, &library_directory, builder));
+    }
+
+    let mut sources = sources;
 
     Ok(ProgramLibrary {
-        programs
+        programs: programs_by_source.into_iter().flatten().collect(),
+        diagnostics,
+        library_file: Some(sources.swap_remove(0).library_file),
+        source_files,
+    })
+}
+
+/// Creates `Program` and replaces variables in the strings of the program.
+///
+/// Variables `library_dir`, `program_name`, environment variables and user defined
+/// variables can be used in every string. Values of user defined variables can only use
+/// `library_dir`, `program_name` and environment variables. Variable `working_dir`
+/// can be used in every string except `working_directory` and user defined variables.
+fn resolve_program(item: &LibraryFileProgram, library_directory: &Path, source: &Path) -> Result<Program, VariableError> {
+    let program_name = item.name.clone();
+
+    let mut variables = Variables::new();
+    variables.insert(variables::LIBRARY_DIR, library_directory.to_string_lossy().into_owned());
+    variables.insert(variables::PROGRAM_NAME, program_name.clone());
+
+    let mut user_variables = vec![];
+
+    for (name, value) in &item.variables {
+        let value = expand(&variables, value, &program_name, &format!("variables.{}", name))?;
+        user_variables.push((name, value));
+    }
+
+    for (name, value) in user_variables {
+        variables.insert(name, value);
+    }
+
+    let working_directory_text = expand(&variables, &item.working_directory, &program_name, "working_directory")?;
+
+    let mut working_directory = library_directory.to_path_buf();
+    // push replaces current path if argument is absolute path
+    working_directory.push(working_directory_text);
+
+    variables.insert(variables::WORKING_DIR, working_directory.to_string_lossy().into_owned());
+
+    let download_command = match item.download_command {
+        Some(ref command) => Some(expand_command(&variables, command, &program_name, "download_command")?),
+        None => None,
+    };
+
+    let mut command_queues = vec![];
+
+    for (queue_i, queue) in item.command_queues.iter().enumerate() {
+        let mut commands = vec![];
+
+        for (command_i, command) in queue.commands.iter().enumerate() {
+            let field = format!("command_queues[{}].commands[{}]", queue_i, command_i);
+            commands.push(expand_command(&variables, command, &program_name, &field)?);
+        }
+
+        command_queues.push(CommandQueue {
+            name: queue.name.clone(),
+            commands,
+            failure_policy: queue.failure_policy,
+            extra: queue.extra.clone(),
+        });
+    }
+
+    let env = expand_env(&variables, &item.env, &program_name, "env")?;
+
+    Ok(Program {
+        name: program_name,
+        download_command,
+        working_directory,
+        command_queues,
+        env,
+        env_remove: item.env_remove.clone(),
+        clear_env: item.clear_env,
+        source: source.to_path_buf(),
+    })
+}
+
+fn expand_command(variables: &Variables, command: &CommandData, program_name: &str, field: &str) -> Result<CommandData, VariableError> {
+    let executable = expand(variables, &command.executable, program_name, &format!("{}.executable", field))?;
+
+    let mut args = vec![];
+
+    for (i, arg) in command.args.iter().enumerate() {
+        args.push(expand(variables, arg, program_name, &format!("{}.args[{}]", field, i))?);
+    }
+
+    let cwd = match command.cwd {
+        Some(ref cwd) => Some(expand(variables, cwd, program_name, &format!("{}.cwd", field))?),
+        None => None,
+    };
+
+    let env = expand_env(variables, &command.env, program_name, &format!("{}.env", field))?;
+
+    Ok(CommandData {
+        executable,
+        args,
+        env,
+        env_remove: command.env_remove.clone(),
+        clear_env: command.clear_env,
+        cwd,
+        extra: command.extra.clone(),
+    })
+}
+
+/// Replaces variables in values of environment variables.
+fn expand_env(variables: &Variables, env: &BTreeMap<String, String>, program_name: &str, field: &str) -> Result<BTreeMap<String, String>, VariableError> {
+    let mut expanded = BTreeMap::new();
+
+    for (key, value) in env {
+        let value = expand(variables, value, program_name, &format!("{}.{}", field, key))?;
+        expanded.insert(key.clone(), value);
+    }
+
+    Ok(expanded)
+}
+
+fn expand(variables: &Variables, text: &str, program_name: &str, field: &str) -> Result<String, VariableError> {
+    variables.expand(text).map_err(|kind| {
+        VariableError {
+            program: program_name.to_string(),
+            field: field.to_string(),
+            kind,
+        }
     })
 }
 
@@ -162,4 +756,207 @@ pub(crate) fn write_empty_file(file_path: &Path) -> Result<(), Error> {
         Ok(_) => Ok(()),
         Err(io_error) => Err(Error::IoError(io_error)),
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    /// Creates an empty directory for a test.
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("program-launcher-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// Library file which uses every field.
+    const LIBRARY: &str = r#"{
+        "version": 1,
+        "programs": [{
+            "name": "A",
+            "download_command": { "executable": "git", "args": ["clone", "url", "a"] },
+            "working_directory": "${directory}",
+            "command_queues": [{
+                "name": "Run",
+                "commands": [{
+                    "executable": "cargo",
+                    "args": ["run", "--release"],
+                    "env": { "RUST_LOG": "debug" },
+                    "env_remove": ["HOME"],
+                    "clear_env": true,
+                    "cwd": "build"
+                }],
+                "failure_policy": { "retry": 3 }
+            }],
+            "env": { "LANG": "C" },
+            "env_remove": ["DISPLAY"],
+            "clear_env": true,
+            "variables": { "directory": "a" }
+        }]
+    }"#;
+
+    #[test]
+    fn library_is_saved_and_loaded_as_toml_and_ron() {
+        let library_directory = test_directory("formats");
+        let library_file: LibraryFile = serde_json::from_str(LIBRARY).unwrap();
+        let expected = serde_json::to_string(&library_file).unwrap();
+
+        for format in &[LibraryFormat::Toml, LibraryFormat::Ron] {
+            let file_path = library_directory.join(format.library_file_name());
+            save_library(&file_path, &library_file, &library_directory).unwrap();
+
+            let library = load_library(&file_path, &library_directory).unwrap();
+            let loaded = LibraryFile {
+                programs: library.library_file_programs().unwrap().to_vec(),
+                ..library_file.clone()
+            };
+
+            assert_eq!(serde_json::to_string(&loaded).unwrap(), expected, "format {:?}", format);
+            assert!(library.diagnostics.iter().all(|diagnostic| !diagnostic.is_error()));
+        }
+
+        fs::remove_dir_all(&library_directory).unwrap();
+    }
+
+    #[test]
+    fn handwritten_ron_library_file() {
+        let text = r#"(
+            version: 1,
+            programs: [
+                (
+                    name: "A",
+                    working_directory: "a",
+                    download_command: Some((executable: "git", args: ["clone", "url", "a"])),
+                    command_queues: [
+                        (
+                            name: "Run",
+                            commands: [(executable: "cargo", args: ["run"], cwd: Some("build"))],
+                            failure_policy: retry(3),
+                        ),
+                    ],
+                ),
+            ],
+        )"#;
+
+        let library_file = ron_format::from_str(text).unwrap();
+        let queue = &library_file.programs[0].command_queues[0];
+
+        assert_eq!(library_file.version, 1);
+        assert_eq!(queue.failure_policy, FailurePolicy::Retry(3));
+        assert_eq!(queue.commands[0].cwd, Some("build".to_string()));
+    }
+
+    #[test]
+    fn default_library_file_can_be_saved_in_every_format() {
+        let library_file: LibraryFile = serde_json::from_str(DEFAULT_LIBRARY_FILE).unwrap();
+
+        for format in LibraryFormat::ALL {
+            let file_path = Path::new("library").with_extension(format.extension());
+            let text = to_text(*format, &library_file).unwrap();
+            let mut diagnostics = vec![];
+
+            assert!(parse_library_file(&file_path, &text, &mut diagnostics).is_some(), "format {:?}: {:?}", format, diagnostics);
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_kept() {
+        let text = r#"[{"name":"A","working_directory":"a","command_queues":[],"icon":"a.png"}]"#;
+        let programs: Vec<LibraryFileProgram> = serde_json::from_str(text).unwrap();
+
+        assert_eq!(serde_json::to_string(&programs).unwrap(), text);
+    }
+
+    #[test]
+    fn library_format_from_extension() {
+        assert_eq!(LibraryFormat::from_path(Path::new("library.d/local.toml")), Some(LibraryFormat::Toml));
+        assert_eq!(LibraryFormat::from_path(Path::new("library.ron")), Some(LibraryFormat::Ron));
+        assert_eq!(LibraryFormat::from_path(Path::new("library.json.bak")), None);
+    }
+
+    #[test]
+    fn includes_are_relative_to_the_including_file() {
+        let library_directory = test_directory("nested-includes");
+        let shared_directory = library_directory.join("shared");
+        create_dir_all(shared_directory.join("games")).unwrap();
+
+        let program = |name: &str| format!(r#"{{"name":"{}","working_directory":"a","command_queues":[]}}"#, name);
+        fs::write(shared_directory.join("library.json"), format!(r#"{{"version": 1, "include": ["games"], "programs": [{}]}}"#, program("B"))).unwrap();
+        fs::write(shared_directory.join("games").join("c.json"), format!(r#"{{"version": 1, "programs": [{}]}}"#, program("C"))).unwrap();
+
+        let file_path = library_directory.join("library.json");
+        let text = format!(r#"{{"version": 1, "include": ["shared/library.json"], "programs": [{}]}}"#, program("A"));
+        let library = parse_library(&file_path, &text, &library_directory);
+
+        fs::remove_dir_all(&library_directory).unwrap();
+
+        let names: Vec<String> = library.unwrap().programs.into_iter().map(|program| program.name).collect();
+        assert_eq!(names, ["A", "B", "C"]);
+    }
+
+    #[test]
+    fn included_files_are_merged() {
+        let library_directory = test_directory("includes");
+        let library_d_directory = library_directory.join(LIBRARY_D_DIRECTORY_NAME);
+        create_dir_all(&library_d_directory).unwrap();
+
+        let program = |name: &str| format!(r#"{{"name":"{}","working_directory":"a","command_queues":[]}}"#, name);
+        fs::write(library_directory.join("shared.json"), format!("[{}, {}]", program("B"), program("C"))).unwrap();
+        fs::write(library_d_directory.join("local.json"), format!("[{}]", program("D"))).unwrap();
+
+        let file_path = library_directory.join("library.json");
+        let text = format!(r#"{{"version": 1, "include": ["shared.json"], "programs": [{}]}}"#, program("A"));
+        let library = parse_library(&file_path, &text, &library_directory).unwrap();
+
+        let names: Vec<&str> = library.programs.iter().map(|program| program.name.as_str()).collect();
+        assert_eq!(names, ["A", "B", "C", "D"]);
+        assert_eq!(library.programs[1].source, library_directory.join("shared.json"));
+        assert_eq!(library.library_file_programs().unwrap().len(), 1);
+
+        let text = format!(r#"{{"version": 1, "include": ["shared.json"], "programs": [{}]}}"#, program("C"));
+        let diagnostics = match parse_library(&file_path, &text, &library_directory) {
+            Err(Error::Validation(diagnostics)) => diagnostics,
+            _ => panic!("name collision was not detected"),
+        };
+
+        fs::remove_dir_all(&library_directory).unwrap();
+
+        let error = diagnostics.iter().find(|diagnostic| diagnostic.is_error()).unwrap();
+        assert_eq!(error.file, library_directory.join("shared.json"));
+        assert_eq!(error.message, format!("program name is also defined in `{}` at line 1", file_path.display()));
+    }
+
+    #[test]
+    fn library_is_saved_atomically() {
+        let library_directory = test_directory("save");
+        let file_path = library_directory.join("library.json");
+        let temp_file_path = library_directory.join("library.json.tmp");
+
+        let library_file: LibraryFile = serde_json::from_str(LIBRARY).unwrap();
+        save_library(&file_path, &library_file, &library_directory).unwrap();
+        let text = fs::read_to_string(&file_path).unwrap();
+        assert!(!temp_file_path.exists());
+
+        // Library with errors is not written.
+        let mut invalid_library_file = library_file.clone();
+        invalid_library_file.programs.push(invalid_library_file.programs[0].clone());
+
+        match save_library(&file_path, &invalid_library_file, &library_directory) {
+            Err(Error::Validation(_)) => (),
+            _ => panic!("invalid library was saved"),
+        }
+
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), text);
+
+        // Temporary file is removed if the file can't be replaced.
+        let directory_path = library_directory.join("directory.json");
+        create_dir_all(directory_path.join("file")).unwrap();
+        assert!(write_file(&directory_path, "[]").is_err());
+        assert!(!library_directory.join("directory.json.tmp").exists());
+
+        fs::remove_dir_all(&library_directory).unwrap();
+    }
+}
@@ -0,0 +1,32 @@
+//! Interrupt signals for cancelling command queues which are run from a terminal.
+//!
+//! Commands run in their own process groups, so the terminal does not send
+//! SIGINT to them when Ctrl+C is pressed.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn handle_signal(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Replaces the default handlers of SIGINT and SIGTERM, so that the
+/// signals are only recorded. Does nothing on other platforms than Unix.
+pub fn install_handler() {
+    #[cfg(unix)]
+    {
+        let handler = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+
+        unsafe {
+            libc::signal(libc::SIGINT, handler);
+            libc::signal(libc::SIGTERM, handler);
+        }
+    }
+}
+
+/// Returns true if SIGINT or SIGTERM was received after the previous call.
+pub fn take_interrupt() -> bool {
+    INTERRUPTED.swap(false, Ordering::SeqCst)
+}
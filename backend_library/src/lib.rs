@@ -2,47 +2,183 @@
 #[macro_use]
 extern crate serde_derive;
 
+extern crate serde;
 extern crate serde_json;
+extern crate toml;
+extern crate ron;
+
+#[cfg(unix)]
+extern crate libc;
 
 pub mod data;
+pub mod interrupt;
+pub mod migration;
+pub mod process_output;
+mod ron_format;
+pub mod run_log;
 pub mod task_manager;
+pub mod validation;
+pub mod variables;
 
 use task_manager::TaskManager;
+use run_log::RunLogInfo;
+use validation::{Diagnostic, Severity};
 
 
-use data::{ProgramLibrary};
+use data::{ProgramLibrary, LibraryFileProgram, LibraryFormat, CommandQueue};
 
-use std::path::{Path};
 use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::{Duration, Instant, SystemTime};
+use std::fs;
+use std::fmt;
 
 pub struct ProgramLibraryManager {
     program_library: ProgramLibrary,
     task_manager: TaskManager,
+    library_directory: PathBuf,
+    library_file_path: PathBuf,
+    /// Library files and directories which are checked for changes.
+    library_files: Vec<PathBuf>,
+    /// Modification times of `library_files` when the library was loaded.
+    library_files_modified: Vec<Option<SystemTime>>,
+    library_file_checked: Instant,
+    events: VecDeque<Event>,
 }
 
-const LIBRARY_FILE_NAME: &'static str = "library.json";
+/// How often the library files are checked for changes.
+const LIBRARY_FILE_CHECK_INTERVAL_MILLISECONDS: u64 = 1000;
 
 impl ProgramLibraryManager {
-    pub fn new(library_directory_name: &str) -> Result<ProgramLibraryManager, Error> {
-        // Handle library directory creation
-        let library_directory = Path::new(library_directory_name).to_path_buf();
-        data::create_library_directory_if_not_exists(library_directory.as_path())?;
+    /// Creates the library directory and its parent directories if they don't exist.
+    ///
+    /// Library file is `library.json`, `library.toml` or `library.ron`. If there
+    /// is more than one library file, the first one in that order is used. Default
+    /// `library.json` is created if there is no library file.
+    ///
+    /// If the library can't be loaded, there are no programs and the error is
+    /// in the library diagnostics.
+    pub fn new<P: AsRef<Path>>(library_directory: P) -> ProgramLibraryManager {
+        let library_directory = library_directory.as_ref().to_path_buf();
+
+        let library_file_path = LibraryFormat::ALL
+            .iter()
+            .map(|format| library_directory.join(format.library_file_name()))
+            .find(|file_path| file_path.exists())
+            .unwrap_or_else(|| library_directory.join(LibraryFormat::Json.library_file_name()));
 
-        // Handle library file creation and loading
-        let mut library_file_path = library_directory.clone();
-        library_file_path.push(LIBRARY_FILE_NAME);
+        let load_result = data::create_library_directory_if_not_exists(library_directory.as_path())
+            .and_then(|_| data::save_default_if_file_not_exists(library_file_path.as_path(), data::DEFAULT_LIBRARY_FILE))
+            .and_then(|_| data::load_library(library_file_path.as_path(), library_directory.as_path()));
 
-        data::save_default_if_file_not_exists(library_file_path.as_path(), data::DEFAULT_LIBRARY_FILE)?;
-        let program_library = data::load_library(library_file_path.as_path(), library_directory.as_path())?;
+        // Start without programs if the library file has errors, so that errors can be displayed.
+        let program_library = match load_result {
+            Ok(program_library) => program_library,
+            Err(Error::Validation(diagnostics)) => ProgramLibrary::empty(diagnostics),
+            Err(error) => {
+                ProgramLibrary::empty(vec![Diagnostic {
+                    severity: Severity::Error,
+                    file: library_file_path.clone(),
+                    position: None,
+                    program: None,
+                    field: None,
+                    message: error.to_string(),
+                }])
+            }
+        };
 
-        let task_manager = TaskManager::new(library_directory);
+        let task_manager = TaskManager::new(library_directory.clone());
 
-        let library_manager = ProgramLibraryManager {
+        let mut library_manager = ProgramLibraryManager {
             program_library,
             task_manager,
+            library_directory,
+            library_file_path,
+            library_files: vec![],
+            library_files_modified: vec![],
+            library_file_checked: Instant::now(),
+            events: VecDeque::new(),
         };
 
-        Ok(library_manager)
+        library_manager.update_library_files();
+
+        library_manager
+    }
+
+    /// Updates the list of library files to check for changes. If the library
+    /// has errors, previous files and files with errors are checked.
+    fn update_library_files(&mut self) {
+        if self.program_library.library_file.is_some() {
+            self.library_files = self.program_library.source_files.clone();
+        } else {
+            for diagnostic in &self.program_library.diagnostics {
+                if !self.library_files.contains(&diagnostic.file) {
+                    self.library_files.push(diagnostic.file.clone());
+                }
+            }
+        }
+
+        let library_d_directory = self.library_directory.join(data::LIBRARY_D_DIRECTORY_NAME);
+
+        for file in &[self.library_file_path.clone(), library_d_directory] {
+            if !self.library_files.contains(file) {
+                self.library_files.push(file.clone());
+            }
+        }
+
+        self.library_files_modified = self.library_files.iter().map(|file| modified_time(file)).collect();
+    }
+
+    /// Reloads the library if the library file, an included file or
+    /// directory `library.d` is modified.
+    ///
+    /// If the new library file has errors, the current programs are kept and
+    /// library diagnostics are replaced with the errors. Running command queue
+    /// is not affected by reloading.
+    fn reload_library_if_modified(&mut self) {
+        if self.library_file_checked.elapsed() < Duration::from_millis(LIBRARY_FILE_CHECK_INTERVAL_MILLISECONDS) {
+            return;
+        }
+
+        self.library_file_checked = Instant::now();
+
+        // File may not exist for a moment when it is saved.
+        if modified_time(&self.library_file_path).is_none() {
+            return;
+        }
+
+        let modified: Vec<Option<SystemTime>> = self.library_files.iter().map(|file| modified_time(file)).collect();
+
+        if modified == self.library_files_modified {
+            return;
+        }
+
+        match data::load_library(&self.library_file_path, &self.library_directory) {
+            Ok(program_library) => {
+                self.program_library = program_library;
+                self.events.push_back(Event::LibraryReloaded);
+            }
+            Err(Error::Validation(diagnostics)) => {
+                self.program_library.diagnostics = diagnostics;
+                self.program_library.library_file = None;
+                self.events.push_back(Event::LibraryReloadFailed);
+            }
+            Err(error) => {
+                self.program_library.library_file = None;
+                self.program_library.diagnostics = vec![Diagnostic {
+                    severity: Severity::Error,
+                    file: self.library_file_path.clone(),
+                    position: None,
+                    program: None,
+                    field: None,
+                    message: error.to_string(),
+                }];
+                self.events.push_back(Event::LibraryReloadFailed);
+            }
+        }
+
+        self.update_library_files();
     }
 
 
@@ -50,26 +186,375 @@ impl ProgramLibraryManager {
         (&mut self.task_manager, &self.program_library)
     }
 
-    pub fn update<'a>(&'a mut self) -> Option<Event<'a>> {
+    pub fn programs(&self) -> &ProgramLibrary {
+        &self.program_library
+    }
+
+    pub fn task_manager(&self) -> &TaskManager {
+        &self.task_manager
+    }
+
+    /// Updates the task manager. Read generated events with `next_event` method.
+    pub fn update(&mut self) {
+        self.reload_library_if_modified();
         self.task_manager.update()
     }
+
+    pub fn add_program(&mut self, program: LibraryFileProgram) -> Result<(), Error> {
+        self.edit_library(|programs| {
+            programs.push(program);
+            Ok(())
+        })
+    }
+
+    pub fn remove_program(&mut self, program_i: usize) -> Result<LibraryFileProgram, Error> {
+        let mut removed = None;
+
+        self.edit_library(|programs| {
+            check_index(programs.len(), program_i)?;
+            removed = Some(programs.remove(program_i));
+            Ok(())
+        })?;
+
+        Ok(removed.unwrap())
+    }
+
+    /// Moves program to index `new_i`.
+    pub fn move_program(&mut self, program_i: usize, new_i: usize) -> Result<(), Error> {
+        self.edit_library(|programs| {
+            move_item(programs, program_i, new_i)
+        })
+    }
+
+    /// Edit program with closure `edit`.
+    pub fn edit_program<F: FnOnce(&mut LibraryFileProgram)>(&mut self, program_i: usize, edit: F) -> Result<(), Error> {
+        self.edit_library(|programs| {
+            check_index(programs.len(), program_i)?;
+            edit(&mut programs[program_i]);
+            Ok(())
+        })
+    }
+
+    pub fn add_command_queue(&mut self, program_i: usize, command_queue: CommandQueue) -> Result<(), Error> {
+        self.edit_library(|programs| {
+            check_index(programs.len(), program_i)?;
+            programs[program_i].command_queues.push(command_queue);
+            Ok(())
+        })
+    }
+
+    pub fn remove_command_queue(&mut self, program_i: usize, queue_i: usize) -> Result<CommandQueue, Error> {
+        let mut removed = None;
+
+        self.edit_library(|programs| {
+            check_index(programs.len(), program_i)?;
+            let command_queues = &mut programs[program_i].command_queues;
+            check_index(command_queues.len(), queue_i)?;
+            removed = Some(command_queues.remove(queue_i));
+            Ok(())
+        })?;
+
+        Ok(removed.unwrap())
+    }
+
+    /// Moves command queue to index `new_i`.
+    pub fn move_command_queue(&mut self, program_i: usize, queue_i: usize, new_i: usize) -> Result<(), Error> {
+        self.edit_library(|programs| {
+            check_index(programs.len(), program_i)?;
+            move_item(&mut programs[program_i].command_queues, queue_i, new_i)
+        })
+    }
+
+    /// Edit command queue with closure `edit`.
+    pub fn edit_command_queue<F: FnOnce(&mut CommandQueue)>(&mut self, program_i: usize, queue_i: usize, edit: F) -> Result<(), Error> {
+        self.edit_library(|programs| {
+            check_index(programs.len(), program_i)?;
+            let command_queues = &mut programs[program_i].command_queues;
+            check_index(command_queues.len(), queue_i)?;
+            edit(&mut command_queues[queue_i]);
+            Ok(())
+        })
+    }
+
+    /// Edits copy of the library file programs and saves them to the library file.
+    /// Library is not changed if the edited library has errors or saving fails.
+    fn edit_library<F: FnOnce(&mut Vec<LibraryFileProgram>) -> Result<(), Error>>(&mut self, edit: F) -> Result<(), Error> {
+        let mut library_file = match self.program_library.library_file {
+            Some(ref library_file) => library_file.clone(),
+            None => return Err(Error::Validation(self.program_library.diagnostics.clone())),
+        };
+
+        edit(&mut library_file.programs)?;
+
+        self.program_library = data::save_library(&self.library_file_path, &library_file, &self.library_directory)?;
+
+        // Prevent reloading the saved file.
+        self.update_library_files();
+
+        Ok(())
+    }
+
+    /// Writes the library file in another format and renames the previous library
+    /// file by adding `.bak` to the file name. Included files are not converted.
+    /// Unknown fields are not kept when the library file is converted to RON.
+    ///
+    /// Returns path of the new library file.
+    pub fn convert_library(&mut self, format: LibraryFormat) -> Result<PathBuf, Error> {
+        let library_file = match self.program_library.library_file {
+            Some(ref library_file) => library_file.clone(),
+            None => return Err(Error::Validation(self.program_library.diagnostics.clone())),
+        };
+
+        let new_file_path = self.library_directory.join(format.library_file_name());
+
+        if new_file_path == self.library_file_path {
+            return Ok(new_file_path);
+        }
+
+        if new_file_path.exists() {
+            return Err(Error::FileExists(new_file_path));
+        }
+
+        let program_library = data::save_library(&new_file_path, &library_file, &self.library_directory)?;
+
+        let mut backup_file_path = self.library_file_path.as_os_str().to_os_string();
+        backup_file_path.push(".bak");
+
+        if let Err(io_error) = fs::rename(&self.library_file_path, backup_file_path) {
+            let _ = fs::remove_file(&new_file_path);
+            return Err(Error::IoError(io_error));
+        }
+
+        self.program_library = program_library;
+        self.library_file_path = new_file_path.clone();
+        self.update_library_files();
+
+        Ok(new_file_path)
+    }
+
+    /// Errors and warnings found when loading the library files.
+    pub fn library_diagnostics(&self) -> &[Diagnostic] {
+        &self.program_library.diagnostics
+    }
+
+    pub fn next_event(&mut self) -> Option<Event> {
+        self.events.pop_front().or_else(|| self.task_manager.next_event())
+    }
+
+    /// Log files of previous command queue runs of a program. Newest log file is the first item.
+    pub fn run_logs(&self, program_name: &str) -> Result<Vec<RunLogInfo>, Error> {
+        run_log::list_run_logs(&self.library_directory.join(run_log::LOGS_DIRECTORY_NAME), program_name)
+    }
+}
+
+fn check_index(len: usize, i: usize) -> Result<(), Error> {
+    if i < len {
+        Ok(())
+    } else {
+        Err(Error::InvalidIndex(i))
+    }
+}
+
+fn move_item<T>(items: &mut Vec<T>, i: usize, new_i: usize) -> Result<(), Error> {
+    check_index(items.len(), i)?;
+    check_index(items.len(), new_i)?;
+
+    let item = items.remove(i);
+    items.insert(new_i, item);
+
+    Ok(())
+}
+
+fn modified_time(file_path: &Path) -> Option<SystemTime> {
+    fs::metadata(file_path).and_then(|metadata| metadata.modified()).ok()
 }
 
 #[derive(Debug)]
 pub enum Error {
     ParseError(serde_json::error::Error),
+    /// Library file could not be serialized.
+    Serialize(String),
     IoError(std::io::Error),
+    /// File already exists and it would be overwritten.
+    FileExists(PathBuf),
+    /// Library file has errors. Contains also warnings.
+    Validation(Vec<Diagnostic>),
+    /// Program or command queue index is out of bounds.
+    InvalidIndex(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::ParseError(ref error) => write!(f, "parse error: {}", error),
+            Error::Serialize(ref error) => write!(f, "serialization error: {}", error),
+            Error::IoError(ref error) => write!(f, "io error: {}", error),
+            Error::FileExists(ref path) => write!(f, "file `{}` already exists", path.display()),
+            Error::InvalidIndex(i) => write!(f, "invalid index {}", i),
+            Error::Validation(ref diagnostics) => {
+                for diagnostic in diagnostics {
+                    writeln!(f, "{}", diagnostic)?;
+                }
+
+                Ok(())
+            }
+        }
+    }
 }
 
 
-pub enum Event<'a> {
-    ConsoleUpdate(&'a VecDeque<String>)
+#[derive(Debug)]
+pub enum Event {
+    /// Console lines were updated, see `TaskManager::console_lines`.
+    ConsoleUpdate,
+    /// New command queue was set. Contains name of the command queue.
+    QueueStarted(String),
+    /// Download command was started. Contains the command as text.
+    DownloadStarted(String),
+    /// Command from the command queue was started. Contains the command as text.
+    CommandStarted(String),
+    CommandExited(ExitStatus),
+    SpawnFailed(std::io::Error),
+    /// There is no more commands in the command queue.
+    QueueFinished {
+        /// True if every command in the queue succeeded.
+        success: bool,
+    },
+    /// Library files were modified and loaded again. Running command queue is not changed.
+    LibraryReloaded,
+    /// Library files were modified, but they have errors. Previous programs are
+    /// kept. See `ProgramLibraryManager::library_diagnostics`.
+    LibraryReloadFailed,
 }
 
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
     #[test]
-    fn it_works() {
+    fn library_is_converted_to_toml_and_ron() {
+        let library_directory = env::temp_dir().join(format!("program-launcher-convert-{}", process::id()));
+        let _ = fs::remove_dir_all(&library_directory);
+
+        let mut library = ProgramLibraryManager::new(&library_directory);
+        let expected = serde_json::to_string(library.programs().library_file_programs().unwrap()).unwrap();
+
+        for format in &[LibraryFormat::Ron, LibraryFormat::Toml, LibraryFormat::Json] {
+            let previous_file_path = library.library_file_path.clone();
+            let file_path = library.convert_library(*format).unwrap();
+
+            assert_eq!(file_path, library_directory.join(format.library_file_name()));
+            assert!(!previous_file_path.exists());
+
+            let mut backup_file_path = previous_file_path.into_os_string();
+            backup_file_path.push(".bak");
+            assert!(PathBuf::from(backup_file_path).exists());
+
+            // Converted library file is used when the library is loaded again.
+            library = ProgramLibraryManager::new(&library_directory);
+            assert_eq!(library.library_file_path, file_path);
+            assert_eq!(serde_json::to_string(library.programs().library_file_programs().unwrap()).unwrap(), expected);
+        }
+
+        fs::remove_dir_all(&library_directory).unwrap();
+    }
+
+    fn test_program(name: &str) -> LibraryFileProgram {
+        LibraryFileProgram {
+            name: name.to_string(),
+            working_directory: name.to_string(),
+            ..LibraryFileProgram::default()
+        }
+    }
+
+    fn program_names(library: &ProgramLibraryManager) -> Vec<String> {
+        library.programs().library_file_programs().unwrap().iter().map(|program| program.name.clone()).collect()
+    }
+
+    #[test]
+    fn programs_are_added_moved_and_removed() {
+        let library_directory = env::temp_dir().join(format!("program-launcher-edit-{}", process::id()));
+        let _ = fs::remove_dir_all(&library_directory);
+
+        let mut library = ProgramLibraryManager::new(&library_directory);
+        let initial_names = program_names(&library);
+        let count = initial_names.len();
+
+        library.add_program(test_program("a")).unwrap();
+        library.add_program(test_program("b")).unwrap();
+        assert_eq!(program_names(&library)[count..], ["a", "b"]);
+
+        library.move_program(count + 1, count).unwrap();
+        assert_eq!(program_names(&library)[count..], ["b", "a"]);
+
+        let removed = library.remove_program(count).unwrap();
+        assert_eq!(removed.name, "b");
+
+        // Changes are saved to the library file.
+        let library = ProgramLibraryManager::new(&library_directory);
+        let mut expected = initial_names;
+        expected.push("a".to_string());
+        assert_eq!(program_names(&library), expected);
+
+        fs::remove_dir_all(&library_directory).unwrap();
+    }
+
+    #[test]
+    fn invalid_index_is_error() {
+        let library_directory = env::temp_dir().join(format!("program-launcher-index-{}", process::id()));
+        let _ = fs::remove_dir_all(&library_directory);
+
+        let mut library = ProgramLibraryManager::new(&library_directory);
+        let count = program_names(&library).len();
+        let text = fs::read_to_string(&library.library_file_path).unwrap();
+
+        match library.remove_program(count) {
+            Err(Error::InvalidIndex(i)) => assert_eq!(i, count),
+            _ => panic!("expected invalid index error"),
+        }
+
+        match library.move_program(0, count) {
+            Err(Error::InvalidIndex(i)) => assert_eq!(i, count),
+            _ => panic!("expected invalid index error"),
+        }
+
+        // Library file is not changed.
+        assert_eq!(fs::read_to_string(&library.library_file_path).unwrap(), text);
+
+        fs::remove_dir_all(&library_directory).unwrap();
+    }
+
+    #[test]
+    fn io_error_is_library_diagnostic() {
+        // Library directory can't be created, because there is a file with the same name.
+        let library_directory = env::temp_dir().join(format!("program-launcher-io-error-{}", process::id()));
+        fs::write(&library_directory, "").unwrap();
+
+        let library = ProgramLibraryManager::new(&library_directory);
+        fs::remove_file(&library_directory).unwrap();
+
+        assert!(library.programs().programs.is_empty());
+        assert!(library.programs().library_file_programs().is_none());
+        assert_eq!(library.library_diagnostics().len(), 1);
+        assert!(library.library_diagnostics()[0].is_error());
+    }
+
+    #[test]
+    fn move_item_keeps_other_items() {
+        let mut items = vec![0, 1, 2, 3];
+
+        move_item(&mut items, 0, 2).unwrap();
+        assert_eq!(items, [1, 2, 0, 3]);
+
+        move_item(&mut items, 3, 0).unwrap();
+        assert_eq!(items, [3, 1, 2, 0]);
+
+        assert!(move_item(&mut items, 4, 0).is_err());
+        assert!(move_item(&mut items, 0, 4).is_err());
+        assert_eq!(items, [3, 1, 2, 0]);
     }
 }
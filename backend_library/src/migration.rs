@@ -0,0 +1,408 @@
+//! Library file versions and upgrading old library files.
+//!
+//! Version 0 library file is the program array or an object without the
+//! `version` field. Version 1 library file is an object with fields
+//! `version` and `programs`.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json::{self, Map, Value};
+use toml;
+
+use Error;
+use data::{self, LibraryFile, LibraryFormat};
+use ron_format;
+use validation::{Diagnostic, Severity};
+
+/// Current version of the library file.
+pub const LIBRARY_VERSION: u32 = 1;
+
+/// Migration from version `i` to version `i + 1` is at index `i`.
+const MIGRATIONS: &[fn(Value) -> Value] = &[
+    version_0_to_1,
+];
+
+/// Moves the program array to field `programs` and replaces `$` with `$$` in
+/// fields which have variable substitution, because version 0 library files
+/// don't have variables.
+fn version_0_to_1(library_file: Value) -> Value {
+    let mut library_file = match library_file {
+        Value::Array(programs) => {
+            let mut object = Map::new();
+            object.insert("programs".to_string(), Value::Array(programs));
+            Value::Object(object)
+        }
+        library_file => library_file,
+    };
+
+    if let Some(&mut Value::Array(ref mut programs)) = library_file.get_mut("programs") {
+        for program in programs {
+            escape_program_variables(program);
+        }
+    }
+
+    library_file
+}
+
+fn escape_program_variables(program: &mut Value) {
+    escape_variables(program.get_mut("working_directory"));
+    escape_map_variables(program.get_mut("variables"));
+    escape_map_variables(program.get_mut("env"));
+
+    if let Some(command) = program.get_mut("download_command") {
+        escape_command_variables(command);
+    }
+
+    if let Some(&mut Value::Array(ref mut queues)) = program.get_mut("command_queues") {
+        for queue in queues {
+            if let Some(&mut Value::Array(ref mut commands)) = queue.get_mut("commands") {
+                for command in commands {
+                    escape_command_variables(command);
+                }
+            }
+        }
+    }
+}
+
+fn escape_command_variables(command: &mut Value) {
+    escape_variables(command.get_mut("executable"));
+    escape_variables(command.get_mut("cwd"));
+    escape_map_variables(command.get_mut("env"));
+
+    if let Some(&mut Value::Array(ref mut args)) = command.get_mut("args") {
+        for arg in args {
+            escape_variables(Some(arg));
+        }
+    }
+}
+
+fn escape_map_variables(map: Option<&mut Value>) {
+    if let Some(&mut Value::Object(ref mut map)) = map {
+        for value in map.values_mut() {
+            escape_variables(Some(value));
+        }
+    }
+}
+
+fn escape_variables(text: Option<&mut Value>) {
+    if let Some(&mut Value::String(ref mut text)) = text {
+        *text = text.replace('$', "$$");
+    }
+}
+
+/// Returns None if field `version` is not a number.
+fn library_file_version(library_file: &Value) -> Option<u64> {
+    match library_file.get("version") {
+        Some(version) => version.as_u64(),
+        None => Some(0),
+    }
+}
+
+fn parse_value(format: LibraryFormat, text: &str) -> Option<Value> {
+    match format {
+        LibraryFormat::Json => serde_json::from_str(text).ok(),
+        LibraryFormat::Toml => toml::from_str(text).ok(),
+        LibraryFormat::Ron => ron_format::to_value(text),
+    }
+}
+
+/// Writes the previous library file to a backup file. Backup file of
+/// `library.json` version 0 is `library.json.v0.bak`. If the file already
+/// exists, a number is added, for example `library.json.v0.1.bak`.
+fn write_backup_file(file_path: &Path, version: u64, text: &str) -> Result<PathBuf, io::Error> {
+    for i in 0.. {
+        let mut backup_file_path = file_path.as_os_str().to_os_string();
+
+        if i == 0 {
+            backup_file_path.push(format!(".v{}.bak", version));
+        } else {
+            backup_file_path.push(format!(".v{}.{}.bak", version, i));
+        }
+
+        let backup_file_path = PathBuf::from(backup_file_path);
+
+        match OpenOptions::new().write(true).create_new(true).open(&backup_file_path) {
+            Ok(mut file) => {
+                file.write_all(text.as_bytes())?;
+                file.sync_all()?;
+                return Ok(backup_file_path);
+            }
+            Err(ref error) if error.kind() == io::ErrorKind::AlreadyExists => (),
+            Err(error) => return Err(error),
+        }
+    }
+
+    unreachable!()
+}
+
+fn file_diagnostic(severity: Severity, file_path: &Path, message: String) -> Diagnostic {
+    Diagnostic {
+        severity,
+        file: file_path.to_path_buf(),
+        position: None,
+        program: None,
+        field: None,
+        message,
+    }
+}
+
+enum Upgrade {
+    /// Library file is the current version or it is not valid. Parsing
+    /// errors are reported when the library file is parsed.
+    NotNeeded,
+    Upgraded {
+        version: u64,
+        text: String,
+    },
+    /// Error is added to the diagnostics.
+    Failed,
+}
+
+fn upgrade(file_path: &Path, text: &str, diagnostics: &mut Vec<Diagnostic>) -> Upgrade {
+    let format = LibraryFormat::from_path(file_path).unwrap_or(LibraryFormat::Json);
+
+    let mut library_file = match parse_value(format, text) {
+        Some(library_file) => library_file,
+        None => return Upgrade::NotNeeded,
+    };
+
+    let version = match library_file_version(&library_file) {
+        Some(version) => version,
+        None => return Upgrade::NotNeeded,
+    };
+
+    if version == u64::from(LIBRARY_VERSION) {
+        return Upgrade::NotNeeded;
+    }
+
+    if version > u64::from(LIBRARY_VERSION) {
+        let message = format!("library file version {} is newer than supported version {}", version, LIBRARY_VERSION);
+        diagnostics.push(file_diagnostic(Severity::Error, file_path, message));
+        return Upgrade::Failed;
+    }
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        library_file = migration(library_file);
+
+        if let Value::Object(ref mut object) = library_file {
+            object.insert("version".to_string(), Value::from(i + 1));
+        }
+    }
+
+    let upgraded_text = serde_json::from_value::<LibraryFile>(library_file)
+        .map_err(Error::ParseError)
+        .and_then(|library_file| data::to_text(format, &library_file));
+
+    match upgraded_text {
+        Ok(text) => Upgrade::Upgraded { version, text },
+        Err(error) => {
+            let message = format!("library file could not be upgraded from version {} to version {}: {}", version, LIBRARY_VERSION, error);
+            diagnostics.push(file_diagnostic(Severity::Error, file_path, message));
+            Upgrade::Failed
+        }
+    }
+}
+
+/// Upgrades the library file to the current version. Previous library file is
+/// written to a backup file before the library file is rewritten. If writing
+/// fails, the upgraded text is still returned.
+///
+/// Returns None if the library file is newer than the current version or if it
+/// can't be upgraded. Text which is not valid is returned unchanged, so that
+/// parsing errors are reported when the library file is parsed.
+pub(crate) fn migrate(file_path: &Path, text: String, diagnostics: &mut Vec<Diagnostic>) -> Option<String> {
+    let (version, upgraded_text) = match upgrade(file_path, &text, diagnostics) {
+        Upgrade::NotNeeded => return Some(text),
+        Upgrade::Upgraded { version, text } => (version, text),
+        Upgrade::Failed => return None,
+    };
+
+    let result = write_backup_file(file_path, version, &text)
+        .map_err(Error::IoError)
+        .and_then(|backup_file_path| {
+            data::write_file(file_path, &upgraded_text).map(|()| backup_file_path)
+        });
+
+    let message = match result {
+        Ok(backup_file_path) => format!("library file was upgraded from version {} to version {}, previous library file was saved to `{}`", version, LIBRARY_VERSION, backup_file_path.display()),
+        Err(error) => format!("library file was upgraded from version {} to version {}, but it could not be saved: {}", version, LIBRARY_VERSION, error),
+    };
+
+    diagnostics.push(file_diagnostic(Severity::Warning, file_path, message));
+
+    Some(upgraded_text)
+}
+
+/// Upgrades included library file to the current version without rewriting
+/// it, because included files may be shared with other users.
+///
+/// Returns None if the library file is newer than the current version or if it
+/// can't be upgraded.
+pub(crate) fn upgrade_included(file_path: &Path, text: String, diagnostics: &mut Vec<Diagnostic>) -> Option<String> {
+    match upgrade(file_path, &text, diagnostics) {
+        Upgrade::NotNeeded => Some(text),
+        Upgrade::Upgraded { version, text } => {
+            let message = format!("included library file version {} is older than version {}, file is upgraded when it is loaded, but it is not rewritten", version, LIBRARY_VERSION);
+            diagnostics.push(file_diagnostic(Severity::Warning, file_path, message));
+            Some(text)
+        }
+        Upgrade::Failed => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    /// Creates an empty directory for a test.
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("program-launcher-migration-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// Migrates version 0 library file and returns the upgraded library file.
+    fn migrate_file(directory: &Path, file_name: &str, text: &str) -> (LibraryFile, Vec<Diagnostic>) {
+        let file_path = directory.join(file_name);
+        fs::write(&file_path, text).unwrap();
+
+        let mut diagnostics = vec![];
+        let upgraded_text = migrate(&file_path, text.to_string(), &mut diagnostics).unwrap();
+
+        assert_eq!(fs::read_to_string(&file_path).unwrap(), upgraded_text);
+
+        let library_file = match LibraryFormat::from_path(&file_path).unwrap() {
+            LibraryFormat::Json => serde_json::from_str(&upgraded_text).unwrap(),
+            LibraryFormat::Toml => toml::from_str(&upgraded_text).unwrap(),
+            LibraryFormat::Ron => ron_format::from_str(&upgraded_text).unwrap(),
+        };
+
+        (library_file, diagnostics)
+    }
+
+    #[test]
+    fn program_array_is_upgraded() {
+        let directory = test_directory("json");
+        let text = r#"[{"name":"A","working_directory":"a","command_queues":[]}]"#;
+
+        let (library_file, diagnostics) = migrate_file(&directory, "library.json", text);
+        let backup_text = fs::read_to_string(directory.join("library.json.v0.bak")).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(library_file.version, LIBRARY_VERSION);
+        assert_eq!(library_file.programs[0].name, "A");
+        assert_eq!(backup_text, text);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn toml_and_ron_files_are_upgraded() {
+        let directory = test_directory("formats");
+
+        let toml_text = "[[programs]]\nname = \"A\"\nworking_directory = \"a\"\ncommand_queues = []\n";
+        let (library_file, _) = migrate_file(&directory, "library.toml", toml_text);
+        assert_eq!(library_file.version, LIBRARY_VERSION);
+        assert_eq!(library_file.programs[0].name, "A");
+
+        let ron_text = r#"[(name: "B", working_directory: "b", command_queues: [])]"#;
+        let (library_file, _) = migrate_file(&directory, "library.ron", ron_text);
+        assert_eq!(library_file.version, LIBRARY_VERSION);
+        assert_eq!(library_file.programs[0].name, "B");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn existing_backup_file_is_not_overwritten() {
+        let directory = test_directory("backup");
+        fs::write(directory.join("library.json.v0.bak"), "previous backup").unwrap();
+
+        migrate_file(&directory, "library.json", "[]");
+
+        let previous_backup = fs::read_to_string(directory.join("library.json.v0.bak")).unwrap();
+        let new_backup = fs::read_to_string(directory.join("library.json.v0.1.bak")).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(previous_backup, "previous backup");
+        assert_eq!(new_backup, "[]");
+    }
+
+    #[test]
+    fn library_file_is_not_rewritten_if_backup_fails() {
+        let directory = test_directory("backup-fails");
+        // Backup file can't be created to a directory which does not exist.
+        let file_path = directory.join("missing").join("library.json");
+
+        let mut diagnostics = vec![];
+        let upgraded_text = migrate(&file_path, "[]".to_string(), &mut diagnostics).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(upgraded_text.contains("\"version\": 1"));
+        assert!(!file_path.exists());
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[0].message.contains("could not be saved"));
+    }
+
+    #[test]
+    fn included_file_is_not_rewritten() {
+        let directory = test_directory("included");
+        let file_path = directory.join("shared.json");
+        fs::write(&file_path, "[]").unwrap();
+
+        let mut diagnostics = vec![];
+        let upgraded_text = upgrade_included(&file_path, "[]".to_string(), &mut diagnostics).unwrap();
+        let file_count = fs::read_dir(&directory).unwrap().count();
+        let text = fs::read_to_string(&file_path).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(upgraded_text.contains("\"version\": 1"));
+        assert_eq!(text, "[]");
+        assert_eq!(file_count, 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn newer_version_is_error() {
+        let mut diagnostics = vec![];
+        let text = r#"{"version": 1000, "programs": []}"#.to_string();
+
+        assert!(migrate(Path::new("library.json"), text, &mut diagnostics).is_none());
+        assert!(diagnostics[0].is_error());
+    }
+
+    #[test]
+    fn dollar_signs_are_escaped() {
+        let directory = test_directory("escape");
+        let text = r#"[{
+            "name": "$A",
+            "working_directory": "${a}",
+            "variables": {"v": "$v"},
+            "env": {"E": "$E"},
+            "download_command": {"executable": "$d", "args": []},
+            "command_queues": [{"name": "$q", "commands": [{"executable": "$e", "args": ["${HOME}", "$$"], "cwd": "$c"}]}]
+        }]"#;
+
+        let (library_file, _) = migrate_file(&directory, "library.json", text);
+        fs::remove_dir_all(&directory).unwrap();
+
+        let program = &library_file.programs[0];
+        assert_eq!(program.name, "$A");
+        assert_eq!(program.working_directory, "$${a}");
+        assert_eq!(program.variables["v"], "$$v");
+        assert_eq!(program.env["E"], "$$E");
+        assert_eq!(program.download_command.as_ref().unwrap().executable, "$$d");
+
+        let queue = &program.command_queues[0];
+        assert_eq!(queue.name, "$q");
+        assert_eq!(queue.commands[0].executable, "$$e");
+        assert_eq!(queue.commands[0].args, ["$${HOME}", "$$$$"]);
+        assert_eq!(queue.commands[0].cwd, Some("$$c".to_string()));
+    }
+}
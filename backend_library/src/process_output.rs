@@ -0,0 +1,227 @@
+//! Line-oriented reading of process output.
+
+use std::io::{BufRead, BufReader, ErrorKind, Read};
+use std::mem;
+use std::str;
+use std::sync::mpsc;
+use std::time::SystemTime;
+
+/// Buffer size for reading process output.
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Max length of a line in bytes. Longer lines are split.
+pub const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// Max count of lines in one message to the main thread.
+const MAX_BATCH_LINES: usize = 1000;
+
+/// Output stream of a process.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputSource {
+    Stdout,
+    Stderr,
+}
+
+/// One line of process output without the line ending.
+#[derive(Debug, Clone)]
+pub struct OutputLine {
+    pub source: OutputSource,
+    pub text: String,
+    /// Time when the line was read.
+    pub time: SystemTime,
+}
+
+/// Reads lines from `reader` and sends them to `transmitter` until end of the stream.
+///
+/// Bytes are buffered until a complete line is available, so lines and multibyte
+/// characters will not be split. Lines which are longer than `MAX_LINE_LENGTH`
+/// bytes are split to multiple lines. Line without a line ending at the end of
+/// the stream is also sent. Invalid UTF-8 is replaced with `U+FFFD`.
+///
+/// Lines are sent in batches. Batch is sent when there is no more buffered
+/// output or when the batch has `MAX_BATCH_LINES` lines.
+pub(crate) fn read_and_send_lines<T: Read>(reader: T, source: OutputSource, transmitter: mpsc::Sender<Vec<OutputLine>>) {
+    let mut reader = BufReader::with_capacity(READ_BUFFER_SIZE, reader);
+    let mut line_bytes = Vec::new();
+    let mut lines = Vec::new();
+
+    loop {
+        let (line_end, consumed) = {
+            let buffer = match reader.fill_buf() {
+                Ok(buffer) => buffer,
+                Err(ref error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    println!("error when reading process output: {}", error);
+                    break;
+                }
+            };
+
+            if buffer.is_empty() {
+                break;
+            }
+
+            let available = &buffer[..buffer.len().min(MAX_LINE_LENGTH - line_bytes.len())];
+
+            match available.iter().position(|&byte| byte == b'\n') {
+                Some(i) => {
+                    line_bytes.extend_from_slice(&available[..i]);
+                    (Some(LineEnd::LineEnding), i + 1)
+                }
+                None => {
+                    line_bytes.extend_from_slice(available);
+
+                    if line_bytes.len() >= MAX_LINE_LENGTH {
+                        (Some(LineEnd::MaxLength), available.len())
+                    } else {
+                        (None, available.len())
+                    }
+                }
+            }
+        };
+
+        reader.consume(consumed);
+
+        match line_end {
+            Some(LineEnd::LineEnding) => {
+                if line_bytes.ends_with(b"\r") {
+                    line_bytes.pop();
+                }
+
+                lines.push(output_line(source, &line_bytes));
+                line_bytes.clear();
+            }
+            Some(LineEnd::MaxLength) => {
+                // Incomplete character is moved to the next line.
+                let length = complete_utf8_length(&line_bytes);
+                lines.push(output_line(source, &line_bytes[..length]));
+                line_bytes.drain(..length);
+            }
+            None => (),
+        }
+
+        let send_batch = reader.buffer().is_empty() || lines.len() >= MAX_BATCH_LINES;
+
+        if !lines.is_empty() && send_batch && !send_lines(&transmitter, &mut lines) {
+            return;
+        }
+    }
+
+    if !line_bytes.is_empty() {
+        lines.push(output_line(source, &line_bytes));
+    }
+
+    if !lines.is_empty() {
+        send_lines(&transmitter, &mut lines);
+    }
+}
+
+enum LineEnd {
+    LineEnding,
+    /// Line has `MAX_LINE_LENGTH` bytes.
+    MaxLength,
+}
+
+fn output_line(source: OutputSource, bytes: &[u8]) -> OutputLine {
+    OutputLine {
+        source,
+        text: String::from_utf8_lossy(bytes).into_owned(),
+        time: SystemTime::now(),
+    }
+}
+
+/// Length of `bytes` without an incomplete UTF-8 character at the end.
+fn complete_utf8_length(bytes: &[u8]) -> usize {
+    match str::from_utf8(bytes) {
+        Err(ref error) if error.error_len().is_none() && error.valid_up_to() > 0 => error.valid_up_to(),
+        _ => bytes.len(),
+    }
+}
+
+/// Returns false if the receiver is disconnected.
+fn send_lines(transmitter: &mpsc::Sender<Vec<OutputLine>>, lines: &mut Vec<OutputLine>) -> bool {
+    if let Err(error) = transmitter.send(mem::take(lines)) {
+        println!("error when transmitting process output to main thread, {}", error);
+        return false;
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{self, Read};
+    use std::sync::mpsc;
+
+    /// Reader which returns only one byte per `read` call.
+    struct OneByteReader(Vec<u8>, usize);
+
+    impl Read for OneByteReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.get(self.1) {
+                Some(&byte) if !buf.is_empty() => {
+                    buf[0] = byte;
+                    self.1 += 1;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn read_lines<T: Read>(reader: T) -> Vec<String> {
+        let (transmitter, receiver) = mpsc::channel();
+        read_and_send_lines(reader, OutputSource::Stderr, transmitter);
+
+        receiver.iter().flatten().map(|line| {
+            assert_eq!(line.source, OutputSource::Stderr);
+            line.text
+        }).collect()
+    }
+
+    #[test]
+    fn line_endings() {
+        let lines = read_lines(&b"first\nsecond\r\n\nlast without line ending"[..]);
+        assert_eq!(lines, vec!["first", "second", "", "last without line ending"]);
+    }
+
+    #[test]
+    fn multibyte_characters_are_not_split() {
+        let reader = OneByteReader("äö\n€\n".as_bytes().to_vec(), 0);
+        assert_eq!(read_lines(reader), vec!["äö", "€"]);
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let lines = read_lines(&b"a\xffb\n"[..]);
+        assert_eq!(lines, vec!["a\u{FFFD}b"]);
+    }
+
+    #[test]
+    fn long_lines_are_split() {
+        let mut text = "a".repeat(MAX_LINE_LENGTH - 1);
+        text.push_str("äb\nc");
+
+        let lines = read_lines(text.as_bytes());
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].len(), MAX_LINE_LENGTH - 1);
+        assert_eq!(lines[1], "äb");
+        assert_eq!(lines[2], "c");
+    }
+
+    #[test]
+    fn lines_are_sent_in_batches() {
+        let line_count = 100_000;
+        let text = "line\n".repeat(line_count);
+
+        let (transmitter, receiver) = mpsc::channel();
+        read_and_send_lines(text.as_bytes(), OutputSource::Stdout, transmitter);
+        let batches: Vec<Vec<OutputLine>> = receiver.iter().collect();
+
+        assert_eq!(batches.iter().map(Vec::len).sum::<usize>(), line_count);
+        // On average at least 50 lines are sent with one message.
+        assert!(batches.len() <= line_count / 50);
+    }
+}
@@ -0,0 +1,194 @@
+//! RON library files.
+//!
+//! RON does not support `#[serde(flatten)]`, so RON library files are read and
+//! written with these types, which don't have the unknown fields. Unknown fields
+//! are not kept when the library file is saved as RON.
+
+use std::collections::BTreeMap;
+
+use serde_json::{self, Value};
+use ron;
+
+use data::{CommandData, CommandQueue, FailurePolicy, LibraryFile, LibraryFileProgram};
+
+#[derive(Deserialize, Serialize)]
+struct RonLibraryFile {
+    /// Version 0 if the field is missing.
+    #[serde(default)]
+    version: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    include: Vec<String>,
+    #[serde(default)]
+    programs: Vec<RonProgram>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct RonProgram {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    download_command: Option<RonCommand>,
+    working_directory: String,
+    command_queues: Vec<RonCommandQueue>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    env_remove: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    clear_env: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    variables: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct RonCommandQueue {
+    name: String,
+    commands: Vec<RonCommand>,
+    #[serde(default, skip_serializing_if = "is_abort")]
+    failure_policy: FailurePolicy,
+}
+
+#[derive(Deserialize, Serialize)]
+struct RonCommand {
+    executable: String,
+    args: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    env_remove: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    clear_env: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cwd: Option<String>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_abort(value: &FailurePolicy) -> bool {
+    *value == FailurePolicy::Abort
+}
+
+impl From<&LibraryFile> for RonLibraryFile {
+    fn from(library_file: &LibraryFile) -> RonLibraryFile {
+        RonLibraryFile {
+            version: library_file.version,
+            include: library_file.include.clone(),
+            programs: library_file.programs.iter().map(RonProgram::from).collect(),
+        }
+    }
+}
+
+impl From<RonLibraryFile> for LibraryFile {
+    fn from(library_file: RonLibraryFile) -> LibraryFile {
+        LibraryFile {
+            version: library_file.version,
+            include: library_file.include,
+            programs: library_file.programs.into_iter().map(LibraryFileProgram::from).collect(),
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl From<&LibraryFileProgram> for RonProgram {
+    fn from(program: &LibraryFileProgram) -> RonProgram {
+        RonProgram {
+            name: program.name.clone(),
+            download_command: program.download_command.as_ref().map(RonCommand::from),
+            working_directory: program.working_directory.clone(),
+            command_queues: program.command_queues.iter().map(RonCommandQueue::from).collect(),
+            env: program.env.clone(),
+            env_remove: program.env_remove.clone(),
+            clear_env: program.clear_env,
+            variables: program.variables.clone(),
+        }
+    }
+}
+
+impl From<RonProgram> for LibraryFileProgram {
+    fn from(program: RonProgram) -> LibraryFileProgram {
+        LibraryFileProgram {
+            name: program.name,
+            download_command: program.download_command.map(CommandData::from),
+            working_directory: program.working_directory,
+            command_queues: program.command_queues.into_iter().map(CommandQueue::from).collect(),
+            env: program.env,
+            env_remove: program.env_remove,
+            clear_env: program.clear_env,
+            variables: program.variables,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl From<&CommandQueue> for RonCommandQueue {
+    fn from(queue: &CommandQueue) -> RonCommandQueue {
+        RonCommandQueue {
+            name: queue.name.clone(),
+            commands: queue.commands.iter().map(RonCommand::from).collect(),
+            failure_policy: queue.failure_policy,
+        }
+    }
+}
+
+impl From<RonCommandQueue> for CommandQueue {
+    fn from(queue: RonCommandQueue) -> CommandQueue {
+        CommandQueue {
+            name: queue.name,
+            commands: queue.commands.into_iter().map(CommandData::from).collect(),
+            failure_policy: queue.failure_policy,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl From<&CommandData> for RonCommand {
+    fn from(command: &CommandData) -> RonCommand {
+        RonCommand {
+            executable: command.executable.clone(),
+            args: command.args.clone(),
+            env: command.env.clone(),
+            env_remove: command.env_remove.clone(),
+            clear_env: command.clear_env,
+            cwd: command.cwd.clone(),
+        }
+    }
+}
+
+impl From<RonCommand> for CommandData {
+    fn from(command: RonCommand) -> CommandData {
+        CommandData {
+            executable: command.executable,
+            args: command.args,
+            env: command.env,
+            env_remove: command.env_remove,
+            clear_env: command.clear_env,
+            cwd: command.cwd,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+pub(crate) fn to_string(library_file: &LibraryFile) -> Result<String, ron::Error> {
+    ron::ser::to_string_pretty(&RonLibraryFile::from(library_file), ron::ser::PrettyConfig::new())
+}
+
+pub(crate) fn from_str(text: &str) -> Result<LibraryFile, ron::error::SpannedError> {
+    ron::de::from_str::<RonLibraryFile>(text).map(LibraryFile::from)
+}
+
+/// Library file of any version as JSON value for migrations. Version 0
+/// library file can also be the program array.
+pub(crate) fn to_value(text: &str) -> Option<Value> {
+    if let Ok(library_file) = from_str(text) {
+        return serde_json::to_value(library_file).ok();
+    }
+
+    let programs: Vec<LibraryFileProgram> = ron::de::from_str::<Vec<RonProgram>>(text)
+        .ok()?
+        .into_iter()
+        .map(LibraryFileProgram::from)
+        .collect();
+
+    serde_json::to_value(programs).ok()
+}
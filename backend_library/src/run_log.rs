@@ -0,0 +1,294 @@
+//! Console output of command queue runs saved to log files.
+//!
+//! Log files are saved to `<library directory>/logs/<program name>/`.
+
+use std::cmp::Reverse;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use task_manager::ConsoleLineSource;
+use Error;
+
+pub const LOGS_DIRECTORY_NAME: &str = "logs";
+
+/// Max count of log files for one program. Oldest log files will be removed.
+pub const MAX_LOG_FILES_PER_PROGRAM: usize = 20;
+
+const LOG_FILE_EXTENSION: &str = "log";
+
+/// Information about a saved log file.
+#[derive(Debug, Clone)]
+pub struct RunLogInfo {
+    pub path: PathBuf,
+    /// Start time of the command queue run.
+    pub started: SystemTime,
+    /// Name of the command queue.
+    pub command_queue_name: String,
+    /// Orders log files which have the same start time.
+    sequence: u32,
+}
+
+impl RunLogInfo {
+    /// Parse log file name `<milliseconds since unix epoch>_<command queue name>.log`.
+    /// If there already was a log file with the same start time, the number
+    /// of the log file is after the time: `<milliseconds>-<number>_<name>.log`.
+    fn from_path(path: PathBuf) -> Option<RunLogInfo> {
+        if path.extension().and_then(|extension| extension.to_str()) != Some(LOG_FILE_EXTENSION) {
+            return None;
+        }
+
+        let (started, sequence, command_queue_name) = {
+            let file_stem = path.file_stem()?.to_str()?;
+            let mut parts = file_stem.splitn(2, '_');
+            let mut time_parts = parts.next()?.splitn(2, '-');
+
+            let milliseconds: u64 = time_parts.next()?.parse().ok()?;
+            let sequence: u32 = match time_parts.next() {
+                Some(sequence) => sequence.parse().ok()?,
+                None => 0,
+            };
+            let command_queue_name = decode_file_name(parts.next().unwrap_or(""));
+
+            (UNIX_EPOCH + Duration::from_millis(milliseconds), sequence, command_queue_name)
+        };
+
+        Some(RunLogInfo {
+            path,
+            started,
+            command_queue_name,
+            sequence,
+        })
+    }
+
+    /// Read the whole log file.
+    pub fn read(&self) -> Result<String, Error> {
+        let mut text = String::new();
+
+        File::open(&self.path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(Error::IoError)?;
+
+        Ok(text)
+    }
+}
+
+/// Writes console lines of one command queue run to a log file.
+pub(crate) struct RunLogWriter {
+    file: BufWriter<File>,
+    started: SystemTime,
+}
+
+impl RunLogWriter {
+    /// Creates new log file and removes old log files if there is
+    /// more than `MAX_LOG_FILES_PER_PROGRAM` log files.
+    pub(crate) fn create(logs_directory: &Path, program_name: &str, command_queue_name: &str) -> Result<RunLogWriter, Error> {
+        let directory = program_logs_directory(logs_directory, program_name);
+        fs::create_dir_all(&directory).map_err(Error::IoError)?;
+
+        let milliseconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs() * 1000 + u64::from(time.subsec_millis()))
+            .unwrap_or(0);
+
+        let command_queue_name = file_name_safe(command_queue_name);
+
+        let started = UNIX_EPOCH + Duration::from_millis(milliseconds);
+
+        // Log files of queues which start in the same millisecond are numbered.
+        let mut sequence = list_logs_in_directory(&directory)?
+            .iter()
+            .filter(|log| log.started == started)
+            .map(|log| log.sequence + 1)
+            .max()
+            .unwrap_or(0);
+
+        let file = loop {
+            let file_name = if sequence == 0 {
+                format!("{:015}_{}.{}", milliseconds, command_queue_name, LOG_FILE_EXTENSION)
+            } else {
+                format!("{:015}-{}_{}.{}", milliseconds, sequence, command_queue_name, LOG_FILE_EXTENSION)
+            };
+
+            match OpenOptions::new().write(true).create_new(true).open(directory.join(file_name)) {
+                Ok(file) => break file,
+                Err(ref error) if error.kind() == io::ErrorKind::AlreadyExists => sequence += 1,
+                Err(error) => return Err(Error::IoError(error)),
+            }
+        };
+
+        remove_old_logs(&directory)?;
+
+        Ok(RunLogWriter {
+            file: BufWriter::new(file),
+            started,
+        })
+    }
+
+    /// Writes the line with seconds since the start of the command queue run.
+    pub(crate) fn write_line(&mut self, time: SystemTime, source: ConsoleLineSource, text: &str) -> Result<(), Error> {
+        let source = match source {
+            ConsoleLineSource::Stdout => "stdout",
+            ConsoleLineSource::Stderr => "stderr",
+            ConsoleLineSource::Launcher => "launcher",
+        };
+
+        let elapsed = time.duration_since(self.started).unwrap_or_default();
+
+        writeln!(self.file, "[{:4}.{:03}] [{}] {}", elapsed.as_secs(), elapsed.subsec_millis(), source, text).map_err(Error::IoError)
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<(), Error> {
+        self.file.flush().map_err(Error::IoError)
+    }
+}
+
+/// List log files of a program. Newest log file is the first item.
+pub fn list_run_logs(logs_directory: &Path, program_name: &str) -> Result<Vec<RunLogInfo>, Error> {
+    let directory = program_logs_directory(logs_directory, program_name);
+
+    if !directory.exists() {
+        return Ok(vec![]);
+    }
+
+    list_logs_in_directory(&directory)
+}
+
+fn remove_old_logs(directory: &Path) -> Result<(), Error> {
+    let logs = list_logs_in_directory(directory)?;
+
+    for log in logs.iter().skip(MAX_LOG_FILES_PER_PROGRAM) {
+        fs::remove_file(&log.path).map_err(Error::IoError)?;
+    }
+
+    Ok(())
+}
+
+fn list_logs_in_directory(directory: &Path) -> Result<Vec<RunLogInfo>, Error> {
+    let mut logs = vec![];
+
+    for entry in fs::read_dir(directory).map_err(Error::IoError)? {
+        let entry = entry.map_err(Error::IoError)?;
+
+        if let Some(log) = RunLogInfo::from_path(entry.path()) {
+            logs.push(log);
+        }
+    }
+
+    logs.sort_by_key(|log| Reverse((log.started, log.sequence)));
+
+    Ok(logs)
+}
+
+fn program_logs_directory(logs_directory: &Path, program_name: &str) -> PathBuf {
+    logs_directory.join(file_name_safe(program_name))
+}
+
+/// Replaces bytes of characters which are not ASCII alphanumeric, `-` or `_`
+/// with `%` and two hexadecimal digits, so that different names will be
+/// different file names.
+fn file_name_safe(name: &str) -> String {
+    let mut file_name = String::new();
+
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+            file_name.push(c);
+        } else {
+            let mut bytes = [0; 4];
+
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                file_name.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+
+    file_name
+}
+
+/// Reverses `file_name_safe`.
+fn decode_file_name(file_name: &str) -> String {
+    let mut bytes = vec![];
+    let mut input = file_name.bytes();
+
+    while let Some(byte) = input.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+
+        let hex: Vec<u8> = input.by_ref().take(2).collect();
+
+        match String::from_utf8(hex).ok().and_then(|hex| u8::from_str_radix(&hex, 16).ok()) {
+            Some(byte) => bytes.push(byte),
+            None => bytes.push(b'%'),
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    /// Creates an empty directory for a test.
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("program-launcher-run-log-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn different_names_are_different_file_names() {
+        assert_eq!(file_name_safe("A B"), "A%20B");
+        assert_eq!(file_name_safe("A_B"), "A_B");
+        assert_eq!(file_name_safe("../ä"), "%2E%2E%2F%C3%A4");
+
+        for name in &["A B", "A_B", "../ä", "100%"] {
+            assert_eq!(decode_file_name(&file_name_safe(name)), *name);
+        }
+    }
+
+    #[test]
+    fn logs_are_listed_newest_first() {
+        let directory = test_directory("list");
+
+        for name in &["First", "Second queue"] {
+            let mut run_log = RunLogWriter::create(&directory, "Program", name).unwrap();
+            run_log.write_line(SystemTime::now(), ConsoleLineSource::Stdout, "output").unwrap();
+            run_log.flush().unwrap();
+        }
+
+        let logs = list_run_logs(&directory, "Program").unwrap();
+        let other_program_logs = list_run_logs(&directory, "Program 2").unwrap();
+        let text = logs[0].read().unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        let names: Vec<&str> = logs.iter().map(|log| log.command_queue_name.as_str()).collect();
+        assert_eq!(names, vec!["Second queue", "First"]);
+        assert!(text.ends_with("] [stdout] output\n"));
+        assert!(other_program_logs.is_empty());
+    }
+
+    #[test]
+    fn oldest_logs_are_removed() {
+        let directory = test_directory("retention");
+
+        for i in 0..MAX_LOG_FILES_PER_PROGRAM + 2 {
+            RunLogWriter::create(&directory, "Program", &i.to_string()).unwrap();
+        }
+
+        let logs = list_run_logs(&directory, "Program").unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        let newest = (MAX_LOG_FILES_PER_PROGRAM + 1).to_string();
+        assert_eq!(logs.len(), MAX_LOG_FILES_PER_PROGRAM);
+        assert_eq!(logs[0].command_queue_name, newest);
+        assert_eq!(logs[MAX_LOG_FILES_PER_PROGRAM - 1].command_queue_name, "2");
+    }
+}
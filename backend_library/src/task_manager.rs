@@ -2,31 +2,207 @@
 
 use std::path::{Path, PathBuf};
 
-use std::process::{Child, Command, Stdio};
-use std::io::{Read};
+use std::process::{Child, Command, Stdio, ExitStatus};
+use std::io;
+use std::mem;
 
-use std::fmt::Write;
+use std::fmt;
 
 use std::sync::mpsc;
 use std::thread;
 
 use std::collections::VecDeque;
 
-const MAX_LINES: usize = 100;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Time between SIGTERM and SIGKILL when cancelling a process.
+const CANCEL_GRACE_PERIOD_MILLISECONDS: u64 = 3000;
+
+/// Command queue name of the download command queue.
+pub const DOWNLOAD_QUEUE_NAME: &str = "Download";
 
 use Event;
+use process_output::{read_and_send_lines, OutputLine, OutputSource};
+use run_log::{RunLogWriter, LOGS_DIRECTORY_NAME};
+
+
+/// Origin of a console line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConsoleLineSource {
+    Stdout,
+    Stderr,
+    /// Message from `TaskManager`.
+    Launcher,
+}
+
+impl From<OutputSource> for ConsoleLineSource {
+    fn from(source: OutputSource) -> ConsoleLineSource {
+        match source {
+            OutputSource::Stdout => ConsoleLineSource::Stdout,
+            OutputSource::Stderr => ConsoleLineSource::Stderr,
+        }
+    }
+}
 
+#[derive(Debug, Clone)]
+pub struct ConsoleLine {
+    pub source: ConsoleLineSource,
+    pub text: String,
+    /// Position of the line in the original order of all console lines.
+    pub number: u64,
+    /// Time when the line was read from the process output or
+    /// when the launcher message was created.
+    pub time: SystemTime,
+}
+
+/// Limits for console lines which are kept in memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConsoleSettings {
+    /// Max count of console lines. Oldest lines are removed first.
+    pub scrollback_lines: usize,
+    /// Max length of a console line in characters.
+    pub max_line_length: usize,
+    pub long_lines: LongLineMode,
+}
+
+impl Default for ConsoleSettings {
+    fn default() -> ConsoleSettings {
+        ConsoleSettings {
+            scrollback_lines: 1000,
+            max_line_length: 500,
+            long_lines: LongLineMode::Wrap,
+        }
+    }
+}
+
+/// How to handle lines which are longer than `ConsoleSettings::max_line_length`.
+///
+/// Log files will contain the original line, unless it is longer than
+/// `process_output::MAX_LINE_LENGTH` bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LongLineMode {
+    /// Split the line to multiple console lines.
+    Wrap,
+    /// Remove the end of the line.
+    Truncate,
+}
+
+/// Result of a finished command.
+#[derive(Debug, Clone)]
+pub struct CommandResult {
+    /// Command as text.
+    pub command: String,
+    /// Starts from 1. Increases when command is retried.
+    pub attempt: u32,
+    pub outcome: CommandOutcome,
+}
+
+#[derive(Debug, Clone)]
+pub enum CommandOutcome {
+    Exited(ExitStatus),
+    /// Error message from process spawning.
+    SpawnFailed(String),
+}
+
+impl CommandResult {
+    pub fn success(&self) -> bool {
+        match self.outcome {
+            CommandOutcome::Exited(exit_status) => exit_status.success(),
+            CommandOutcome::SpawnFailed(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for CommandResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.outcome {
+            CommandOutcome::Exited(exit_status) => write!(f, "{}: {}", self.command, exit_status)?,
+            CommandOutcome::SpawnFailed(ref error) => write!(f, "{}: error: {}", self.command, error)?,
+        }
+
+        if self.attempt > 1 {
+            write!(f, " (attempt {})", self.attempt)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct CancelState {
+    started: Instant,
+    /// Process ID of the cancelled process, which is also the process group ID.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    process_group_id: u32,
+    kill_sent: bool,
+}
+
+impl CancelState {
+    /// Sends SIGKILL to the process group if the grace period has ended.
+    fn kill_after_grace_period(&mut self) {
+        let grace_period = Duration::from_millis(CANCEL_GRACE_PERIOD_MILLISECONDS);
+
+        if !self.kill_sent && self.started.elapsed() >= grace_period {
+            #[cfg(unix)]
+            send_signal_to_process_group(self.process_group_id, libc::SIGKILL);
+
+            self.kill_sent = true;
+        }
+    }
+}
+
+struct QueuedCommand {
+    command: Command,
+    attempt: u32,
+    download: bool,
+    /// Error which prevents starting the command. It is reported as
+    /// a spawning error when the command would be started.
+    error: Option<String>,
+}
+
+impl QueuedCommand {
+    fn new(data: &CommandData, working_dir: &Path, program: &Program) -> QueuedCommand {
+        let (command, error) = match data.to_command(working_dir, program) {
+            Ok(command) => (command, None),
+            Err(error) => {
+                let mut command = Command::new(&data.executable);
+                command.args(&data.args);
+                (command, Some(error))
+            }
+        };
+
+        QueuedCommand {
+            command,
+            attempt: 1,
+            download: false,
+            error,
+        }
+    }
+}
 
 pub struct TaskManager {
-    queue: Vec<Command>,
+    queue: Vec<QueuedCommand>,
+    failure_policy: FailurePolicy,
+    command_results: Vec<CommandResult>,
+    running_command: Option<QueuedCommand>,
     running_process: Option<Child>,
-    console_lines: VecDeque<String>,
-    byte_vec: Vec<u8>,
+    cancel_state: Option<CancelState>,
+    /// Cancelled processes which exited before the grace period ended. Other
+    /// processes of their process groups are killed when the grace period ends.
+    exited_cancel_states: Vec<CancelState>,
+    queue_running: bool,
+    events: VecDeque<Event>,
+    console_updated: bool,
+    console_lines: VecDeque<ConsoleLine>,
+    console_settings: ConsoleSettings,
+    next_console_line_number: u64,
+    /// Console lines which are not yet read with `take_new_console_lines`.
+    /// None if new lines are not collected.
+    new_console_lines: Option<Vec<ConsoleLine>>,
     stdout_thread_handle: Option<thread::JoinHandle<()>>,
     stderr_thread_handle: Option<thread::JoinHandle<()>>,
-    stdout_receiver: Option<mpsc::Receiver<u8>>,
-    stderr_receiver: Option<mpsc::Receiver<u8>>,
+    output_receiver: Option<mpsc::Receiver<Vec<OutputLine>>>,
     library_directory: PathBuf,
+    run_log: Option<RunLogWriter>,
 }
 
 
@@ -34,201 +210,773 @@ impl TaskManager {
     pub fn new(library_directory: PathBuf) -> TaskManager {
         TaskManager {
             queue: vec![],
+            failure_policy: FailurePolicy::default(),
+            command_results: vec![],
+            running_command: None,
             console_lines: VecDeque::new(),
+            console_settings: ConsoleSettings::default(),
+            next_console_line_number: 0,
+            new_console_lines: None,
             running_process: None,
-            byte_vec: vec![],
-            stdout_receiver: None,
-            stderr_receiver: None,
+            cancel_state: None,
+            exited_cancel_states: vec![],
+            queue_running: false,
+            events: VecDeque::new(),
+            console_updated: false,
+            output_receiver: None,
             stderr_thread_handle: None,
             stdout_thread_handle: None,
             library_directory,
+            run_log: None,
         }
     }
 
-    pub fn console_lines(&self) -> &VecDeque<String> {
+    pub fn console_lines(&self) -> &VecDeque<ConsoleLine> {
         &self.console_lines
     }
 
+    /// Starts collecting new console lines, so that all lines can be read
+    /// with `take_new_console_lines` even if the scrollback limit removes them
+    /// from `console_lines`.
+    pub fn collect_new_console_lines(&mut self) {
+        if self.new_console_lines.is_none() {
+            self.new_console_lines = Some(vec![]);
+        }
+    }
+
+    /// Returns console lines which were added after the previous call.
+    /// Returns empty vector if `collect_new_console_lines` is not called.
+    pub fn take_new_console_lines(&mut self) -> Vec<ConsoleLine> {
+        match self.new_console_lines {
+            Some(ref mut lines) => mem::take(lines),
+            None => vec![],
+        }
+    }
 
-    /// Return new console text if there is new text
-    pub fn update<'a>(&'a mut self) -> Option<Event<'a>> {
-        let mut process_finished = false;
+    pub fn console_settings(&self) -> ConsoleSettings {
+        self.console_settings
+    }
 
-        if let Some(ref mut child) = self.running_process {
-            if let Ok(Some(exit_status)) = child.try_wait() {
-                // TODO: if exit status is non zero, clear command queue
-                println!("{}", exit_status);
-                process_finished = true;
+    /// Sets new console line limits. Already existing lines are
+    /// removed if there is more lines than the new scrollback limit.
+    pub fn set_console_settings(&mut self, settings: ConsoleSettings) {
+        self.console_settings = settings;
+        self.remove_lines_over_scrollback_limit();
+        self.console_updated = true;
+    }
 
-                if let Some(thread_handle) = self.stdout_thread_handle.take() {
-                    thread_handle.join().unwrap();
-                }
+    fn remove_lines_over_scrollback_limit(&mut self) {
+        while self.console_lines.len() > self.console_settings.scrollback_lines {
+            self.console_lines.pop_front();
+        }
+    }
 
-                if let Some(thread_handle) = self.stderr_thread_handle.take() {
-                    thread_handle.join().unwrap();
-                }
+    fn push_console_line(&mut self, source: ConsoleLineSource, text: String) {
+        self.push_console_line_with_time(source, text, SystemTime::now());
+    }
+
+    fn push_console_line_with_time(&mut self, source: ConsoleLineSource, text: String, time: SystemTime) {
+        let mut log_error = None;
+
+        if let Some(ref mut run_log) = self.run_log {
+            if let Err(error) = run_log.write_line(time, source, &text) {
+                log_error = Some(error);
             }
         }
 
-        if process_finished {
-            self.running_process = None
+        if let Some(error) = log_error {
+            // Log file is closed first, so the message is only written to the console.
+            self.run_log = None;
+            self.push_launcher_message(format!("error when writing log file: {}", error));
         }
 
-        let mut stdout_or_stderr_update = false;
+        let max_length = self.console_settings.max_line_length.max(1);
 
-        self.byte_vec.clear();
+        if text.chars().count() <= max_length {
+            self.push_console_line_without_limits(source, text, time);
+            return;
+        }
 
-        if process_finished {
-            if let Some(stdout) = self.stdout_receiver.take() {
-                for byte in stdout.iter() {
-                    self.byte_vec.push(byte);
-                    stdout_or_stderr_update = true;
-                }
+        match self.console_settings.long_lines {
+            LongLineMode::Truncate => {
+                let truncated = text.chars().take(max_length).collect();
+                self.push_console_line_without_limits(source, truncated, time);
             }
+            LongLineMode::Wrap => {
+                let chars: Vec<char> = text.chars().collect();
 
-            if let Some(stderr) = self.stderr_receiver.take() {
-                for byte in stderr.iter() {
-                    self.byte_vec.push(byte);
-                    stdout_or_stderr_update = true;
+                for part in chars.chunks(max_length) {
+                    self.push_console_line_without_limits(source, part.iter().collect(), time);
                 }
             }
-        } else {
-            if let Some(ref stdout) = self.stdout_receiver {
-                for byte in stdout.try_iter() {
-                    self.byte_vec.push(byte);
-                    stdout_or_stderr_update = true;
+        }
+    }
+
+    fn push_console_line_without_limits(&mut self, source: ConsoleLineSource, text: String, time: SystemTime) {
+        let line = ConsoleLine {
+            source,
+            text,
+            number: self.next_console_line_number,
+            time,
+        };
+
+        if let Some(ref mut lines) = self.new_console_lines {
+            lines.push(line.clone());
+        }
+
+        self.console_lines.push_back(line);
+
+        self.next_console_line_number += 1;
+        self.console_updated = true;
+
+        self.remove_lines_over_scrollback_limit();
+    }
+
+    /// Adds empty line and the message to the console.
+    fn push_launcher_message(&mut self, text: String) {
+        self.push_console_line(ConsoleLineSource::Launcher, String::new());
+        self.push_console_line(ConsoleLineSource::Launcher, text);
+    }
+
+    /// Returns true if there is a running process.
+    pub fn running(&self) -> bool {
+        self.running_process.is_some()
+    }
+
+    /// Clears the command queue and stops the running process.
+    ///
+    /// The process group of the running process will receive SIGTERM first
+    /// and SIGKILL after a grace period. SIGKILL is sent even if the process
+    /// has exited, so that processes which it started are also stopped.
+    /// On other platforms than Unix the process is killed immediately.
+    pub fn cancel(&mut self) {
+        self.queue.clear();
+
+        if self.cancel_state.is_some() {
+            return;
+        }
+
+        if self.running_process.is_some() {
+            self.push_launcher_message("Cancelling the running process.".to_string());
+        }
+
+        if let Some(ref mut child) = self.running_process {
+            #[cfg(unix)]
+            send_signal_to_process_group(child.id(), libc::SIGTERM);
+
+            #[cfg(not(unix))]
+            {
+                if let Err(error) = child.kill() {
+                    println!("error when killing process: {}", error);
                 }
             }
 
-            if let Some(ref stderr) = self.stderr_receiver {
-                for byte in stderr.try_iter() {
-                    self.byte_vec.push(byte);
-                    stdout_or_stderr_update = true;
+            self.cancel_state = Some(CancelState {
+                started: Instant::now(),
+                process_group_id: child.id(),
+                kill_sent: false,
+            });
+        }
+    }
+
+    /// Results of finished commands from the current command queue.
+    pub fn command_results(&self) -> &[CommandResult] {
+        &self.command_results
+    }
+
+    /// Returns next event generated by `update` method.
+    pub fn next_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    /// Handles process output and exits, and starts new processes from the command queue.
+    ///
+    /// Command is finished when the process has exited and its output has
+    /// ended. Output is not waited for, because processes which it started
+    /// may keep the output open.
+    ///
+    /// Generated events can be read with `next_event` method.
+    pub fn update(&mut self) {
+        let mut process_exit_status = None;
+
+        if let Some(ref mut child) = self.running_process {
+            if let Ok(Some(exit_status)) = child.try_wait() {
+                process_exit_status = Some(exit_status);
+            }
+        }
+
+        if let Some(ref mut cancel_state) = self.cancel_state {
+            cancel_state.kill_after_grace_period();
+        }
+
+        for cancel_state in &mut self.exited_cancel_states {
+            cancel_state.kill_after_grace_period();
+        }
+
+        self.exited_cancel_states.retain(|cancel_state| !cancel_state.kill_sent);
+
+        if let Some(receiver) = self.output_receiver.take() {
+            loop {
+                match receiver.try_recv() {
+                    Ok(lines) => {
+                        for line in lines {
+                            self.push_console_line_with_time(line.source.into(), line.text, line.time);
+                        }
+                    }
+                    Err(mpsc::TryRecvError::Empty) => {
+                        self.output_receiver = Some(receiver);
+                        break;
+                    }
+                    // Both reader threads have reached the end of the output.
+                    Err(mpsc::TryRecvError::Disconnected) => break,
                 }
             }
         }
 
-        if stdout_or_stderr_update {
-            for line in String::from_utf8_lossy(&self.byte_vec).lines() {
-                self.console_lines.push_back(line.to_string());
+        if self.output_receiver.is_none() {
+            if let Some(thread_handle) = self.stdout_thread_handle.take() {
+                thread_handle.join().unwrap();
             }
 
-            while self.console_lines.len() > MAX_LINES {
-                self.console_lines.pop_front();
+            if let Some(thread_handle) = self.stderr_thread_handle.take() {
+                thread_handle.join().unwrap();
             }
+        } else {
+            process_exit_status = None;
         }
 
-        let update_console = if let Some(Event::ConsoleUpdate(_)) = self.pop_and_execute() {
-            true
-        } else {
-            false
-        };
+        if process_exit_status.is_some() {
+            self.running_process = None
+        }
 
-        if update_console || stdout_or_stderr_update {
-            Some(Event::ConsoleUpdate(&self.console_lines))
-        } else {
-            None
+        if let Some(exit_status) = process_exit_status {
+            if let Some(queued_command) = self.running_command.take() {
+                self.command_finished(queued_command, CommandOutcome::Exited(exit_status));
+            }
+
+            self.events.push_back(Event::CommandExited(exit_status));
+
+            if let Some(cancel_state) = self.cancel_state.take() {
+                if !cancel_state.kill_sent {
+                    self.exited_cancel_states.push(cancel_state);
+                }
+            }
+        }
+
+        self.pop_and_execute();
+
+        if self.queue_running && self.queue.is_empty() && self.running_process.is_none() {
+            self.queue_running = false;
+
+            let success = self.command_results.iter().all(|result| result.success());
+            self.events.push_back(Event::QueueFinished { success });
+
+            if let Some(mut run_log) = self.run_log.take() {
+                if let Err(error) = run_log.flush() {
+                    self.push_launcher_message(format!("error when writing log file: {}", error));
+                }
+            }
+        }
+
+        if self.console_updated {
+            self.console_updated = false;
+            self.events.push_back(Event::ConsoleUpdate);
         }
     }
 
     /// Starts new process from the queue if there not currently exists an running process.
-    pub fn pop_and_execute<'a>(&'a mut self) -> Option<Event<'a>> {
+    pub fn pop_and_execute(&mut self) {
         if self.running_process.is_some() {
-            return None;
+            return;
         }
 
-        if let Some(mut command) = self.queue.pop() {
-            let mut text = String::new();
-            writeln!(text, "\nStarted program: {:?}", command).unwrap();
-            self.console_lines.push_back(text);
+        if let Some(mut queued_command) = self.queue.pop() {
+            self.push_launcher_message(format!("Started program: {:?}", queued_command.command));
+
+            let command_text = format!("{:?}", queued_command.command);
+
+            if queued_command.download {
+                self.events.push_back(Event::DownloadStarted(command_text));
+            } else {
+                self.events.push_back(Event::CommandStarted(command_text));
+            }
+
+            let spawn_result = match queued_command.error {
+                Some(ref error) => Err(io::Error::new(io::ErrorKind::InvalidInput, error.clone())),
+                None => queued_command.command.spawn(),
+            };
 
-            match command.spawn() {
+            match spawn_result {
                 Ok(mut child) => {
+                    // Both threads send to the same channel, so lines are
+                    // received in the order they were read.
+                    let (transmitter, receiver) = mpsc::channel();
+                    self.output_receiver = Some(receiver);
+
                     if let Some(stdout) = child.stdout {
                         child.stdout = None;
 
-                        let (transmitter, receiver) = mpsc::channel();
-                        self.stdout_receiver = Some(receiver);
+                        let transmitter = transmitter.clone();
 
                         // This thread should automatically close when process exits.
                         self.stdout_thread_handle = Some(thread::spawn(move || {
-                            read_and_send_process_output(stdout, transmitter);
+                            read_and_send_lines(stdout, OutputSource::Stdout, transmitter);
                         }));
                     }
 
                     if let Some(stderr) = child.stderr {
                         child.stderr = None;
 
-                        let (transmitter, receiver) = mpsc::channel();
-                        self.stderr_receiver = Some(receiver);
-
                         // This thread should automatically close when process exits.
                         self.stderr_thread_handle = Some(thread::spawn(move || {
-                            read_and_send_process_output(stderr, transmitter);
+                            read_and_send_lines(stderr, OutputSource::Stderr, transmitter);
                         }));
                     }
 
                     self.running_process = Some(child);
+                    self.running_command = Some(queued_command);
                 }
                 Err(error) => {
-                    // TODO: clear command queue
+                    self.command_finished(queued_command, CommandOutcome::SpawnFailed(error.to_string()));
+                    self.events.push_back(Event::SpawnFailed(error));
+                }
+            }
+        }
+    }
+
+    /// Saves the command result and applies the failure policy to the command queue.
+    fn command_finished(&mut self, mut queued_command: QueuedCommand, outcome: CommandOutcome) {
+        let result = CommandResult {
+            command: format!("{:?}", queued_command.command),
+            attempt: queued_command.attempt,
+            outcome,
+        };
+
+        self.push_launcher_message(result.to_string());
 
-                    println!("error: {}", error);
+        let success = result.success();
+        self.command_results.push(result);
 
-                    let mut text = String::new();
-                    writeln!(text, "\nerror: {}", error).unwrap();
-                    self.console_lines.push_back(text);
+        if success || self.cancel_state.is_some() {
+            return;
+        }
+
+        match self.failure_policy {
+            FailurePolicy::Abort => self.clear_queue_after_failure(),
+            FailurePolicy::Continue => (),
+            FailurePolicy::Retry(retry_count) => {
+                if queued_command.attempt <= retry_count {
+                    queued_command.attempt += 1;
+                    self.queue.push(queued_command);
+                } else {
+                    self.clear_queue_after_failure();
                 }
             }
         }
+    }
 
-        Some(Event::ConsoleUpdate(&self.console_lines))
+    fn clear_queue_after_failure(&mut self) {
+        if !self.queue.is_empty() {
+            self.queue.clear();
+            self.push_console_line(ConsoleLineSource::Launcher, "Command failed, rest of the command queue was cleared.".to_string());
+        }
     }
 
     /// Sets new commands to queue if there is not process running. If working directory is not found
     /// the download command will be first command in queue if there exists one.
-    pub fn new_queue_if_no_running_process(&mut self, command_queue: &[CommandData], working_dir: &Path, download_command: &Option<CommandData>) {
+    ///
+    /// Console output of the command queue will be saved to a log file.
+    pub fn new_queue_if_no_running_process(&mut self, program: &Program, command_queue: &CommandQueue) {
         if self.running_process.is_some() {
             return;
         }
 
-        self.queue.clear();
+        let working_dir = program.working_directory.as_path();
+        let download_command = &program.download_command;
+
+        let mut queue = vec![];
 
         if !working_dir.exists() {
             if let &Some(ref command_data) = download_command {
-                self.queue.push(command_data.to_command(self.library_directory.as_path()));
+                let mut queued_command = QueuedCommand::new(command_data, self.library_directory.as_path(), program);
+                queued_command.download = true;
+                queue.push(queued_command);
             }
         }
 
-        for data in command_queue {
-            self.queue.push(data.to_command(working_dir));
+        for data in &command_queue.commands {
+            queue.push(QueuedCommand::new(data, working_dir, program));
         }
 
-        self.queue.reverse();
+        self.start_queue(program, &command_queue.name, queue, command_queue.failure_policy);
     }
-}
 
-fn read_and_send_process_output<T: Read>(reader: T, transmitter: mpsc::Sender<u8>) {
-    for byte_result in reader.bytes() {
-        match byte_result {
-            Ok(byte) => if let Err(error) = transmitter.send(byte) {
-                println!("error when transmitting process output to main thread, {}", error);
-                break;
-            },
+    /// Sets the download command of the program to queue if there is not process running.
+    /// The download command is used even if the working directory exists.
+    ///
+    /// Returns false if the program does not have a download command or there is a running process.
+    pub fn new_download_queue_if_no_running_process(&mut self, program: &Program) -> bool {
+        if self.running_process.is_some() {
+            return false;
+        }
+
+        let command_data = match program.download_command {
+            Some(ref command_data) => command_data,
+            None => return false,
+        };
+
+        let mut queued_command = QueuedCommand::new(command_data, self.library_directory.as_path(), program);
+        queued_command.download = true;
+
+        self.start_queue(program, DOWNLOAD_QUEUE_NAME, vec![queued_command], FailurePolicy::Abort);
+
+        true
+    }
+
+    /// Replaces the queue with `queue` and creates a log file for the queue.
+    fn start_queue(&mut self, program: &Program, queue_name: &str, mut queue: Vec<QueuedCommand>, failure_policy: FailurePolicy) {
+        // Commands are popped from the end.
+        queue.reverse();
+
+        self.queue = queue;
+        self.command_results.clear();
+        self.failure_policy = failure_policy;
+
+        self.queue_running = true;
+        self.events.push_back(Event::QueueStarted(queue_name.to_string()));
+
+        let logs_directory = self.library_directory.join(LOGS_DIRECTORY_NAME);
+
+        self.run_log = match RunLogWriter::create(&logs_directory, &program.name, queue_name) {
+            Ok(run_log) => Some(run_log),
             Err(error) => {
-                println!("error when reading process output: {}", error);
-                break;
+                self.push_launcher_message(format!("error when creating log file: {}", error));
+                None
             }
+        };
+    }
+}
+
+/// Sends signal to the process group which was created for a child process.
+/// Process group ID is the same as the child's process ID, see `CommandData::to_command`.
+///
+/// Process group which does not exist anymore is not an error.
+#[cfg(unix)]
+fn send_signal_to_process_group(process_group_id: u32, signal: libc::c_int) {
+    if unsafe { libc::kill(-(process_group_id as libc::pid_t), signal) } == -1 {
+        let error = io::Error::last_os_error();
+
+        if error.raw_os_error() != Some(libc::ESRCH) {
+            println!("error when sending signal to process group: {}", error);
         }
     }
 }
 
-use data::{CommandData};
+use data::{CommandData, CommandQueue, FailurePolicy, Program};
+use validation;
 
 impl CommandData {
-    fn to_command(&self, working_dir: &Path) -> Command {
+    /// Creates command which runs in `working_dir` or in `cwd` directory relative to it.
+    /// Environment variables of the program are applied before command's
+    /// environment variables. If the command clears the environment, environment
+    /// variables of the program are not used.
+    ///
+    /// Returns error if `cwd` is not inside `working_dir`.
+    fn to_command(&self, working_dir: &Path, program: &Program) -> Result<Command, String> {
         let mut command = Command::new(&self.executable);
+
+        let working_dir = match self.cwd {
+            Some(ref cwd) if validation::is_relative_path_inside(Path::new(cwd)) => working_dir.join(cwd),
+            Some(ref cwd) => return Err(format!("working directory `{}` is not a relative path inside the program's working directory", cwd)),
+            None => working_dir.to_path_buf(),
+        };
+
         command.args(&self.args).current_dir(working_dir).stdout(Stdio::piped()).stderr(Stdio::piped());
-        command
+
+        if self.clear_env {
+            command.env_clear();
+        } else {
+            if program.clear_env {
+                command.env_clear();
+            }
+
+            for key in &program.env_remove {
+                command.env_remove(key);
+            }
+
+            command.envs(&program.env);
+        }
+
+        for key in &self.env_remove {
+            command.env_remove(key);
+        }
+
+        command.envs(&self.env);
+
+        // Create new process group, so cancelling will also stop
+        // the processes that the command starts.
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+
+        Ok(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task_manager_with_settings(long_lines: LongLineMode) -> TaskManager {
+        let mut task_manager = TaskManager::new(PathBuf::new());
+
+        task_manager.set_console_settings(ConsoleSettings {
+            scrollback_lines: 3,
+            max_line_length: 4,
+            long_lines,
+        });
+
+        task_manager
+    }
+
+    fn console_text(task_manager: &TaskManager) -> Vec<&str> {
+        task_manager.console_lines().iter().map(|line| line.text.as_str()).collect()
+    }
+
+    #[test]
+    fn long_lines_are_wrapped() {
+        let mut task_manager = task_manager_with_settings(LongLineMode::Wrap);
+        task_manager.push_console_line(ConsoleLineSource::Stdout, "abcdäöx".to_string());
+
+        assert_eq!(console_text(&task_manager), vec!["abcd", "äöx"]);
+    }
+
+    #[test]
+    fn wrapped_lines_keep_output_time() {
+        let mut task_manager = task_manager_with_settings(LongLineMode::Wrap);
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        task_manager.push_console_line_with_time(ConsoleLineSource::Stdout, "abcdefg".to_string(), time);
+
+        assert!(task_manager.console_lines().iter().all(|line| line.time == time));
+    }
+
+    #[test]
+    fn long_lines_are_truncated() {
+        let mut task_manager = task_manager_with_settings(LongLineMode::Truncate);
+        task_manager.push_console_line(ConsoleLineSource::Stdout, "abcdefg".to_string());
+
+        assert_eq!(console_text(&task_manager), vec!["abcd"]);
+    }
+
+    #[test]
+    fn scrollback_limit_removes_oldest_lines() {
+        let mut task_manager = task_manager_with_settings(LongLineMode::Wrap);
+
+        for text in &["1", "2", "3", "4"] {
+            task_manager.push_console_line(ConsoleLineSource::Stderr, text.to_string());
+        }
+
+        assert_eq!(console_text(&task_manager), vec!["2", "3", "4"]);
+        assert_eq!(task_manager.console_lines()[0].number, 1);
+    }
+
+    /// Returns true if the process exists and it is not a zombie process.
+    #[cfg(target_os = "linux")]
+    fn process_running(process_id: &str) -> bool {
+        match ::std::fs::read_to_string(format!("/proc/{}/stat", process_id)) {
+            Ok(stat) => !stat.contains(") Z "),
+            Err(_) => false,
+        }
+    }
+
+    /// Starts shell `script` which prints the process ID of a background
+    /// process, and returns the process ID.
+    #[cfg(target_os = "linux")]
+    fn start_background_process(directory: &Path, script: &str) -> (TaskManager, String) {
+        ::std::fs::create_dir_all(directory).unwrap();
+
+        let command = CommandData {
+            executable: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            env: Default::default(),
+            env_remove: vec![],
+            clear_env: false,
+            cwd: None,
+            extra: Default::default(),
+        };
+
+        let program = Program {
+            name: "Program".to_string(),
+            download_command: None,
+            working_directory: directory.to_path_buf(),
+            command_queues: vec![],
+            env: Default::default(),
+            env_remove: vec![],
+            clear_env: false,
+            source: PathBuf::new(),
+        };
+
+        let command_queue = CommandQueue {
+            name: "Queue".to_string(),
+            commands: vec![command],
+            failure_policy: FailurePolicy::Abort,
+            extra: Default::default(),
+        };
+
+        let mut task_manager = TaskManager::new(directory.to_path_buf());
+        task_manager.collect_new_console_lines();
+        task_manager.new_queue_if_no_running_process(&program, &command_queue);
+
+        loop {
+            task_manager.update();
+
+            let line = task_manager.take_new_console_lines().into_iter().find(|line| line.source == ConsoleLineSource::Stdout);
+
+            if let Some(line) = line {
+                return (task_manager, line.text);
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn process_group_is_killed_after_process_exits() {
+        let directory = ::std::env::temp_dir().join(format!("program-launcher-cancel-{}", ::std::process::id()));
+
+        // Shell exits when it receives SIGTERM, but the background process ignores it.
+        // Output of the background process is redirected, so that reading
+        // the output of the shell ends when the shell exits.
+        let script = "(trap '' TERM; exec sleep 30) > /dev/null 2>&1 & echo $!; wait";
+        let (mut task_manager, background_process_id) = start_background_process(&directory, script);
+        let wait = || thread::sleep(Duration::from_millis(10));
+
+        task_manager.cancel();
+
+        while task_manager.running() {
+            task_manager.update();
+            wait();
+        }
+
+        let running_after_exit = process_running(&background_process_id);
+        let started = Instant::now();
+
+        while process_running(&background_process_id) && started.elapsed() < Duration::from_secs(10) {
+            task_manager.update();
+            wait();
+        }
+
+        let running_after_grace_period = process_running(&background_process_id);
+        ::std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(running_after_exit);
+        assert!(!running_after_grace_period);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn process_group_is_killed_when_it_keeps_output_open() {
+        let directory = ::std::env::temp_dir().join(format!("program-launcher-cancel-output-{}", ::std::process::id()));
+
+        // Background process keeps the output of the shell open and ignores SIGTERM.
+        let script = "(trap '' TERM; exec sleep 30) & echo $!; wait";
+        let (mut task_manager, background_process_id) = start_background_process(&directory, script);
+
+        task_manager.cancel();
+
+        let started = Instant::now();
+
+        while task_manager.running() && started.elapsed() < Duration::from_secs(10) {
+            let update_started = Instant::now();
+            task_manager.update();
+            assert!(update_started.elapsed() < Duration::from_secs(1));
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let running = task_manager.running();
+        let background_process_running = process_running(&background_process_id);
+        ::std::fs::remove_dir_all(&directory).unwrap();
+
+        assert!(!running);
+        assert!(!background_process_running);
+    }
+
+    fn command_data(cwd: Option<&str>, clear_env: bool) -> CommandData {
+        let mut env = ::std::collections::BTreeMap::new();
+        env.insert("COMMAND".to_string(), "1".to_string());
+
+        CommandData {
+            executable: "true".to_string(),
+            args: vec![],
+            env,
+            env_remove: vec![],
+            clear_env,
+            cwd: cwd.map(|cwd| cwd.to_string()),
+            extra: Default::default(),
+        }
+    }
+
+    fn program_with_env() -> Program {
+        let mut env = ::std::collections::BTreeMap::new();
+        env.insert("PROGRAM".to_string(), "1".to_string());
+
+        Program {
+            name: "Program".to_string(),
+            download_command: None,
+            working_directory: PathBuf::from("program"),
+            command_queues: vec![],
+            env,
+            env_remove: vec![],
+            clear_env: false,
+            source: PathBuf::new(),
+        }
+    }
+
+    fn env_keys(command: &Command) -> Vec<String> {
+        command.get_envs().map(|(key, _)| key.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn command_clear_env_skips_program_env() {
+        let program = program_with_env();
+
+        let command = command_data(None, false).to_command(Path::new("program"), &program).unwrap();
+        assert_eq!(env_keys(&command), vec!["COMMAND", "PROGRAM"]);
+
+        let command = command_data(None, true).to_command(Path::new("program"), &program).unwrap();
+        assert_eq!(env_keys(&command), vec!["COMMAND"]);
+    }
+
+    #[test]
+    fn cwd_must_be_inside_working_directory() {
+        let program = program_with_env();
+        let to_command = |cwd| command_data(Some(cwd), false).to_command(Path::new("program"), &program);
+
+        let command = to_command("a/../b").unwrap();
+        assert_eq!(command.get_current_dir(), Some(Path::new("program/a/../b")));
+
+        assert!(to_command("..").is_err());
+        assert!(to_command("a/../..").is_err());
+        assert!(to_command("/tmp").is_err());
+    }
+
+    #[test]
+    fn new_lines_are_collected_over_scrollback_limit() {
+        let mut task_manager = task_manager_with_settings(LongLineMode::Wrap);
+        task_manager.push_console_line(ConsoleLineSource::Stdout, "0".to_string());
+        task_manager.collect_new_console_lines();
+
+        for text in &["1", "2", "3", "4", "5"] {
+            task_manager.push_console_line(ConsoleLineSource::Stdout, text.to_string());
+        }
+
+        let new_lines: Vec<String> = task_manager.take_new_console_lines().into_iter().map(|line| line.text).collect();
+
+        assert_eq!(new_lines, vec!["1", "2", "3", "4", "5"]);
+        assert!(task_manager.take_new_console_lines().is_empty());
     }
 }
@@ -0,0 +1,535 @@
+//! Library file validation.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use serde_json;
+
+use data::{CommandData, LibraryFileProgram, Program};
+use variables::VariableError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// Library can't be loaded.
+    Error,
+    /// Library can be loaded, but something may not work.
+    Warning,
+}
+
+/// Line and column in a file. Both start from 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FilePosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Problem found from the library file.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: PathBuf,
+    pub position: Option<FilePosition>,
+    pub program: Option<String>,
+    /// Path to the field inside the program, for example `command_queues[0].commands`.
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+
+        if let Some(position) = self.position {
+            write!(f, ":{}:{}", position.line, position.column)?;
+        }
+
+        match self.severity {
+            Severity::Error => write!(f, ": error: ")?,
+            Severity::Warning => write!(f, ": warning: ")?,
+        }
+
+        if let Some(ref program) = self.program {
+            write!(f, "program `{}`, ", program)?;
+        }
+
+        if let Some(ref field) = self.field {
+            write!(f, "field `{}`: ", field)?;
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Creates diagnostics with file positions.
+pub(crate) struct DiagnosticBuilder<'a> {
+    file: &'a Path,
+    text: &'a str,
+    /// Byte offsets of JSON values. Key is path to the value, for example `[0].command_queues[1].name`.
+    offsets: HashMap<String, usize>,
+    /// Path to the program array. Empty if the file contains only the program array.
+    programs_path: &'static str,
+}
+
+impl<'a> DiagnosticBuilder<'a> {
+    /// Argument `text` should be valid JSON.
+    pub(crate) fn new(file: &'a Path, text: &'a str) -> DiagnosticBuilder<'a> {
+        let mut offsets = HashMap::new();
+        let mut scanner = JsonScanner { text, position: 0 };
+        scanner.value(String::new(), &mut offsets);
+
+        let programs_path = if text.trim_start().starts_with('{') {
+            "programs"
+        } else {
+            ""
+        };
+
+        DiagnosticBuilder {
+            file,
+            text,
+            offsets,
+            programs_path,
+        }
+    }
+
+    /// Builder for files which are not JSON. Diagnostics don't have file positions.
+    pub(crate) fn without_positions(file: &'a Path) -> DiagnosticBuilder<'a> {
+        DiagnosticBuilder {
+            file,
+            text: "",
+            offsets: HashMap::new(),
+            programs_path: "",
+        }
+    }
+
+    /// Diagnostic for TOML or RON parsing error.
+    pub(crate) fn format_error(file: &Path, position: Option<FilePosition>, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            file: file.to_path_buf(),
+            position,
+            program: None,
+            field: None,
+            message,
+        }
+    }
+
+    /// Diagnostic for JSON parsing error.
+    pub(crate) fn parse_error(file: &Path, error: &serde_json::Error) -> Diagnostic {
+        let position = if error.line() == 0 {
+            None
+        } else {
+            Some(FilePosition {
+                line: error.line(),
+                column: error.column(),
+            })
+        };
+
+        Diagnostic {
+            severity: Severity::Error,
+            file: file.to_path_buf(),
+            position,
+            program: None,
+            field: None,
+            message: error.to_string(),
+        }
+    }
+
+    /// Diagnostic for field of a program. If position of the field is not found, position
+    /// of the closest parent field is used.
+    pub(crate) fn program_field(&self, severity: Severity, program_i: usize, program_name: &str, field: &str, message: String) -> Diagnostic {
+        let path = format!("{}[{}].{}", self.programs_path, program_i, field);
+
+        Diagnostic {
+            severity,
+            file: self.file.to_path_buf(),
+            position: self.closest_position(path),
+            program: Some(program_name.to_string()),
+            field: Some(field.to_string()),
+            message,
+        }
+    }
+
+    /// Diagnostic for item of the include list.
+    pub(crate) fn include_error(&self, include_i: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            file: self.file.to_path_buf(),
+            position: self.closest_position(format!("include[{}]", include_i)),
+            program: None,
+            field: None,
+            message,
+        }
+    }
+
+    /// Position of the value or its closest parent value.
+    fn closest_position(&self, mut path: String) -> Option<FilePosition> {
+        let offset = loop {
+            if let Some(&offset) = self.offsets.get(&path) {
+                break Some(offset);
+            }
+
+            match path.rfind(['.', '[']) {
+                Some(i) if i > 0 => path.truncate(i),
+                _ => break None,
+            }
+        };
+
+        offset.map(|offset| file_position(self.text, offset))
+    }
+
+    pub(crate) fn variable_error(&self, program_i: usize, error: &VariableError) -> Diagnostic {
+        self.program_field(Severity::Error, program_i, &error.program, &error.field, error.kind.to_string())
+    }
+}
+
+/// Line and column of byte offset in the text.
+pub(crate) fn file_position(text: &str, offset: usize) -> FilePosition {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+    FilePosition {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+    }
+}
+
+/// Diagnostic for a file which could not be read.
+pub(crate) fn file_error(file: &Path, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Error,
+        file: file.to_path_buf(),
+        position: None,
+        program: None,
+        field: None,
+        message,
+    }
+}
+
+/// Checks which can be done before variable substitution.
+pub(crate) fn check_library_file_programs(programs: &[LibraryFileProgram], builder: &DiagnosticBuilder) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut names: HashMap<&str, usize> = HashMap::new();
+
+    for (program_i, program) in programs.iter().enumerate() {
+        if program.name.trim().is_empty() {
+            diagnostics.push(builder.program_field(Severity::Error, program_i, &program.name, "name", "program name is empty".to_string()));
+        }
+
+        if let Some(&first_i) = names.get(program.name.as_str()) {
+            let first = builder.program_field(Severity::Error, first_i, &program.name, "name", String::new());
+
+            let message = match first.position {
+                Some(position) => format!("duplicate program name, first defined at line {}", position.line),
+                None => "duplicate program name".to_string(),
+            };
+
+            diagnostics.push(builder.program_field(Severity::Error, program_i, &program.name, "name", message));
+        } else {
+            names.insert(&program.name, program_i);
+        }
+
+        if let Some(ref command) = program.download_command {
+            if command.executable.trim().is_empty() {
+                diagnostics.push(builder.program_field(Severity::Error, program_i, &program.name, "download_command.executable", "executable is empty".to_string()));
+            }
+        }
+
+        for (queue_i, queue) in program.command_queues.iter().enumerate() {
+            if queue.commands.is_empty() {
+                let field = format!("command_queues[{}].commands", queue_i);
+                let message = format!("command queue `{}` does not have any commands", queue.name);
+                diagnostics.push(builder.program_field(Severity::Error, program_i, &program.name, &field, message));
+            }
+
+            for (command_i, command) in queue.commands.iter().enumerate() {
+                if command.executable.trim().is_empty() {
+                    let field = format!("command_queues[{}].commands[{}].executable", queue_i, command_i);
+                    diagnostics.push(builder.program_field(Severity::Error, program_i, &program.name, &field, "executable is empty".to_string()));
+                }
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Checks for programs which variables are substituted.
+pub(crate) fn check_programs(programs: &[Program], library_directory: &Path, builder: &DiagnosticBuilder) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let library_directory = normalize(library_directory);
+
+    for (program_i, program) in programs.iter().enumerate() {
+        if !normalize(&program.working_directory).starts_with(&library_directory) {
+            let message = format!("working directory `{}` is outside of the library directory", program.working_directory.display());
+            diagnostics.push(builder.program_field(Severity::Warning, program_i, &program.name, "working_directory", message));
+        }
+
+        if let Some(ref command) = program.download_command {
+            if !executable_exists(&command.executable) {
+                let message = format!("executable `{}` is not found from PATH", command.executable);
+                diagnostics.push(builder.program_field(Severity::Warning, program_i, &program.name, "download_command.executable", message));
+            }
+
+            if let Some(message) = cwd_error(command) {
+                diagnostics.push(builder.program_field(Severity::Error, program_i, &program.name, "download_command.cwd", message));
+            }
+        }
+
+        for (queue_i, queue) in program.command_queues.iter().enumerate() {
+            for (command_i, command) in queue.commands.iter().enumerate() {
+                if !executable_exists(&command.executable) {
+                    let field = format!("command_queues[{}].commands[{}].executable", queue_i, command_i);
+                    let message = format!("executable `{}` is not found from PATH", command.executable);
+                    diagnostics.push(builder.program_field(Severity::Warning, program_i, &program.name, &field, message));
+                }
+
+                if let Some(message) = cwd_error(command) {
+                    let field = format!("command_queues[{}].commands[{}].cwd", queue_i, command_i);
+                    diagnostics.push(builder.program_field(Severity::Error, program_i, &program.name, &field, message));
+                }
+            }
+        }
+    }
+
+    diagnostics
+}
+
+fn cwd_error(command: &CommandData) -> Option<String> {
+    match command.cwd {
+        Some(ref cwd) if !is_relative_path_inside(Path::new(cwd)) => {
+            Some(format!("working directory `{}` is not a relative path inside the program's working directory", cwd))
+        }
+        _ => None,
+    }
+}
+
+/// Returns true if executable is found from PATH. Executables with a
+/// path are not checked, because they may be created by other commands.
+fn executable_exists(executable: &str) -> bool {
+    if Path::new(executable).components().count() > 1 {
+        return true;
+    }
+
+    let paths = match env::var_os("PATH") {
+        Some(paths) => paths,
+        None => return false,
+    };
+
+    env::split_paths(&paths).any(|directory| {
+        let file = directory.join(executable);
+
+        file.is_file() || (cfg!(windows) && file.with_extension("exe").is_file())
+    })
+}
+
+/// Returns true if the path is relative and `..` components don't
+/// move it outside of the directory which it is relative to.
+pub(crate) fn is_relative_path_inside(path: &Path) -> bool {
+    let mut depth = 0;
+
+    for component in path.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => (),
+            Component::ParentDir if depth > 0 => depth -= 1,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+
+    true
+}
+
+/// Removes `.` and `..` components from the path without accessing the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component.as_os_str()),
+        }
+    }
+
+    normalized
+}
+
+/// Finds byte offsets of values from valid JSON.
+struct JsonScanner<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> JsonScanner<'a> {
+    fn value(&mut self, path: String, offsets: &mut HashMap<String, usize>) {
+        self.skip_whitespace();
+        offsets.insert(path.clone(), self.position);
+
+        match self.peek() {
+            Some(b'{') => {
+                self.position += 1;
+
+                loop {
+                    self.skip_whitespace();
+
+                    match self.peek() {
+                        Some(b'"') => (),
+                        Some(b'}') | None => break,
+                        Some(_) => self.position += 1,
+                    }
+
+                    if self.peek() == Some(b'"') {
+                        let key = self.string();
+                        self.skip_whitespace();
+                        self.position += 1; // :
+
+                        let child_path = if path.is_empty() {
+                            key
+                        } else {
+                            format!("{}.{}", path, key)
+                        };
+
+                        self.value(child_path, offsets);
+                    }
+                }
+
+                self.position += 1;
+            }
+            Some(b'[') => {
+                self.position += 1;
+                let mut i = 0;
+
+                loop {
+                    self.skip_whitespace();
+
+                    match self.peek() {
+                        Some(b']') | None => break,
+                        Some(b',') => self.position += 1,
+                        Some(_) => {
+                            self.value(format!("{}[{}]", path, i), offsets);
+                            i += 1;
+                        }
+                    }
+                }
+
+                self.position += 1;
+            }
+            Some(b'"') => {
+                self.string();
+            }
+            Some(_) => {
+                while let Some(byte) = self.peek() {
+                    match byte {
+                        b',' | b']' | b'}' | b' ' | b'\t' | b'\r' | b'\n' => break,
+                        _ => self.position += 1,
+                    }
+                }
+            }
+            None => (),
+        }
+    }
+
+    /// Reads string and returns its value.
+    fn string(&mut self) -> String {
+        let start = self.position;
+        self.position += 1;
+
+        while let Some(byte) = self.peek() {
+            self.position += 1;
+
+            match byte {
+                b'\\' => self.position += 1,
+                b'"' => break,
+                _ => (),
+            }
+        }
+
+        let end = self.position.min(self.text.len());
+
+        serde_json::from_str(&self.text[start..end]).unwrap_or_default()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\r') | Some(b'\n') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.position).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    const LIBRARY: &str = r#"[
+    {
+        "name": "A",
+        "working_directory": "a",
+        "command_queues": [
+            { "name": "Run", "commands": [] }
+        ]
+    },
+    {
+        "name": "A",
+        "working_directory": "../a",
+        "command_queues": []
+    }
+]"#;
+
+    fn diagnostics() -> Vec<Diagnostic> {
+        let file = Path::new("library.json");
+        let builder = DiagnosticBuilder::new(file, LIBRARY);
+        let programs: Vec<LibraryFileProgram> = serde_json::from_str(LIBRARY).unwrap();
+
+        check_library_file_programs(&programs, &builder)
+    }
+
+    #[test]
+    fn empty_command_list() {
+        let diagnostics = diagnostics();
+        let diagnostic = &diagnostics[0];
+
+        assert!(diagnostic.is_error());
+        assert_eq!(diagnostic.field, Some("command_queues[0].commands".to_string()));
+        assert_eq!(diagnostic.position, Some(FilePosition { line: 6, column: 42 }));
+    }
+
+    #[test]
+    fn duplicate_program_name() {
+        let diagnostics = diagnostics();
+        let diagnostic = &diagnostics[1];
+
+        assert_eq!(diagnostic.position, Some(FilePosition { line: 10, column: 17 }));
+        assert_eq!(diagnostic.message, "duplicate program name, first defined at line 3");
+        assert_eq!(diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn normalize_path() {
+        assert_eq!(normalize(Path::new("/library/./a/../../b")), Path::new("/b"));
+    }
+
+    #[test]
+    fn relative_path_inside() {
+        assert!(is_relative_path_inside(Path::new("a/./b/..")));
+        assert!(is_relative_path_inside(Path::new("")));
+        assert!(!is_relative_path_inside(Path::new("a/../..")));
+        assert!(!is_relative_path_inside(Path::new("../a")));
+        assert!(!is_relative_path_inside(Path::new("/a")));
+    }
+}
@@ -0,0 +1,140 @@
+//! Variable substitution for strings in the library file.
+//!
+//! Variable `${name}` is replaced with the value of the variable and
+//! `${env:NAME}` with the value of environment variable `NAME`.
+//! Use `$$` to write `$`.
+//!
+//! Version 0 library files don't have variables. When they are upgraded,
+//! `$` is replaced with `$$`, so their strings keep their meaning.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+
+/// Library directory as absolute path.
+pub const LIBRARY_DIR: &str = "library_dir";
+/// Working directory of the program as absolute path.
+pub const WORKING_DIR: &str = "working_dir";
+pub const PROGRAM_NAME: &str = "program_name";
+
+const ENV_PREFIX: &str = "env:";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableErrorKind {
+    UndefinedVariable(String),
+    UndefinedEnvironmentVariable(String),
+    /// Variable without closing `}`.
+    UnterminatedVariable,
+}
+
+impl fmt::Display for VariableErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VariableErrorKind::UndefinedVariable(ref name) => write!(f, "undefined variable `{}`", name),
+            VariableErrorKind::UndefinedEnvironmentVariable(ref name) => write!(f, "undefined environment variable `{}`", name),
+            VariableErrorKind::UnterminatedVariable => write!(f, "missing `}}` after `${{`"),
+        }
+    }
+}
+
+/// Variable substitution error with location of the error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariableError {
+    pub program: String,
+    /// Path to the field, for example `command_queues[0].commands[1].args[0]`.
+    pub field: String,
+    pub kind: VariableErrorKind,
+}
+
+impl fmt::Display for VariableError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "program `{}`, field `{}`: {}", self.program, self.field, self.kind)
+    }
+}
+
+/// Variables which can be used in substitution.
+pub(crate) struct Variables {
+    values: BTreeMap<String, String>,
+}
+
+impl Variables {
+    pub(crate) fn new() -> Variables {
+        Variables {
+            values: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, name: &str, value: String) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    /// Replaces variables in `text` with their values.
+    pub(crate) fn expand(&self, text: &str) -> Result<String, VariableErrorKind> {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(i) = rest.find('$') {
+            result.push_str(&rest[..i]);
+            rest = &rest[i..];
+
+            if rest.starts_with("$$") {
+                result.push('$');
+                rest = &rest[2..];
+            } else if rest.starts_with("${") {
+                let end = rest.find('}').ok_or(VariableErrorKind::UnterminatedVariable)?;
+                let name = &rest[2..end];
+
+                result.push_str(&self.value(name)?);
+                rest = &rest[end + 1..];
+            } else {
+                result.push('$');
+                rest = &rest[1..];
+            }
+        }
+
+        result.push_str(rest);
+
+        Ok(result)
+    }
+
+    fn value(&self, name: &str) -> Result<String, VariableErrorKind> {
+        if let Some(env_name) = name.strip_prefix(ENV_PREFIX) {
+            env::var(env_name).map_err(|_| VariableErrorKind::UndefinedEnvironmentVariable(env_name.to_string()))
+        } else {
+            self.values.get(name).cloned().ok_or_else(|| VariableErrorKind::UndefinedVariable(name.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> Variables {
+        let mut variables = Variables::new();
+        variables.insert(PROGRAM_NAME, "Space Boss Battles".to_string());
+        variables.insert("repository", "space_boss_battles".to_string());
+        variables
+    }
+
+    #[test]
+    fn expand_variables() {
+        let text = variables().expand("${program_name}: ${repository}/target, $$HOME, $5").unwrap();
+        assert_eq!(text, "Space Boss Battles: space_boss_battles/target, $HOME, $5");
+    }
+
+    #[test]
+    fn expand_environment_variable() {
+        env::set_var("PROGRAM_LAUNCHER_VARIABLE_TEST", "value");
+        assert_eq!(variables().expand("${env:PROGRAM_LAUNCHER_VARIABLE_TEST}").unwrap(), "value");
+    }
+
+    #[test]
+    fn errors() {
+        let variables = variables();
+
+        assert_eq!(variables.expand("${unknown}"), Err(VariableErrorKind::UndefinedVariable("unknown".to_string())));
+        assert_eq!(variables.expand("${env:PROGRAM_LAUNCHER_UNDEFINED}"), Err(VariableErrorKind::UndefinedEnvironmentVariable("PROGRAM_LAUNCHER_UNDEFINED".to_string())));
+        assert_eq!(variables.expand("${repository"), Err(VariableErrorKind::UnterminatedVariable));
+    }
+}
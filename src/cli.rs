@@ -0,0 +1,386 @@
+//! Command line interface for using the library without the GUI.
+
+use backend_library::{interrupt, Event, ProgramLibraryManager};
+use backend_library::data::{LibraryFormat, Program};
+use backend_library::task_manager::{CommandOutcome, ConsoleLineSource, ConsoleSettings, LongLineMode, TaskManager};
+
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+pub const USAGE: &str = "\
+Usage:
+    program-launcher [options] [--fullscreen]           Start the GUI.
+    program-launcher [options] list                     List programs, their library files and
+                                                        command queues.
+    program-launcher [options] run <program> <queue>    Run a command queue.
+    program-launcher [options] download <program>       Run the download command of a program.
+    program-launcher [options] convert <format>         Rewrite the library file in format json,
+                                                        toml or ron. Previous library file is
+                                                        renamed to <file>.bak.
+    program-launcher help                               Show this message.
+
+Options:
+    --library-dir <directory>    Library directory. Overrides PROGRAM_LAUNCHER_LIBRARY
+                                 environment variable and the settings file. Default
+                                 directory is $XDG_DATA_HOME/program-launcher.";
+
+/// Exit code for invalid arguments.
+pub const EXIT_CODE_USAGE: i32 = 2;
+
+/// Exit code when the library could not be loaded or a command failed without an exit code.
+const EXIT_CODE_FAILURE: i32 = 1;
+
+/// Exit code when a command could not be started.
+const EXIT_CODE_SPAWN_FAILED: i32 = 127;
+
+/// Exit code when the command queue was cancelled with SIGINT or SIGTERM.
+const EXIT_CODE_INTERRUPTED: i32 = 130;
+
+/// How often the task manager is updated when running commands.
+const UPDATE_INTERVAL_MILLISECONDS: u64 = 16;
+
+pub enum CliCommand {
+    Gui {
+        full_screen: bool,
+    },
+    List,
+    Run {
+        program: String,
+        queue: String,
+    },
+    Download {
+        program: String,
+    },
+    Convert {
+        format: LibraryFormat,
+    },
+    Help,
+}
+
+pub struct Arguments {
+    pub command: CliCommand,
+    /// Value of `--library-dir` option.
+    pub library_directory: Option<String>,
+}
+
+/// Parses command line arguments without the executable name.
+pub fn parse_arguments<T: Iterator<Item=String>>(mut args: T) -> Result<Arguments, String> {
+    let mut library_directory = None;
+    let mut full_screen = false;
+    let mut positional = vec![];
+
+    while let Some(arg) = args.next() {
+        if arg == "--library-dir" {
+            library_directory = Some(args.next().ok_or("missing value for `--library-dir`")?);
+        } else if let Some(directory) = arg.strip_prefix("--library-dir=") {
+            library_directory = Some(directory.to_string());
+        } else if arg == "--fullscreen" {
+            full_screen = true;
+        } else {
+            positional.push(arg);
+        }
+    }
+
+    if library_directory.as_deref() == Some("") {
+        return Err("empty value for `--library-dir`".to_string());
+    }
+
+    let mut positional = positional.into_iter();
+
+    let command = match positional.next() {
+        None => CliCommand::Gui { full_screen },
+        Some(command) => {
+            if full_screen {
+                return Err("`--fullscreen` can only be used when starting the GUI".to_string());
+            }
+
+            match command.as_str() {
+                "list" => CliCommand::List,
+                "run" => {
+                    let program = positional.next().ok_or("missing program name")?;
+                    let queue = positional.next().ok_or("missing command queue name")?;
+                    CliCommand::Run { program, queue }
+                }
+                "download" => {
+                    let program = positional.next().ok_or("missing program name")?;
+                    CliCommand::Download { program }
+                }
+                "convert" => {
+                    let name = positional.next().ok_or("missing library format")?;
+
+                    match LibraryFormat::from_name(&name) {
+                        Some(format) => CliCommand::Convert { format },
+                        None => return Err(format!("unknown library format `{}`, expected json, toml or ron", name)),
+                    }
+                }
+                "help" | "--help" | "-h" => CliCommand::Help,
+                _ => return Err(format!("unknown command `{}`", command)),
+            }
+        }
+    };
+
+    if let Some(arg) = positional.next() {
+        return Err(format!("unexpected argument `{}`", arg));
+    }
+
+    Ok(Arguments {
+        command,
+        library_directory,
+    })
+}
+
+/// Runs a command which does not need the GUI. Returns the exit code.
+pub fn run(command: CliCommand, library_directory: &Path) -> i32 {
+    if let CliCommand::Help = command {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    let mut library = ProgramLibraryManager::new(library_directory);
+
+    let mut library_errors = false;
+
+    for diagnostic in library.library_diagnostics() {
+        eprintln!("{}", diagnostic);
+        library_errors |= diagnostic.is_error();
+    }
+
+    if library_errors {
+        return EXIT_CODE_FAILURE;
+    }
+
+    match command {
+        CliCommand::List => {
+            list_programs(&library.programs().programs);
+            0
+        }
+        CliCommand::Run { program, queue } => {
+            {
+                let (task_manager, programs) = library.task_manager_mut_and_programs();
+
+                let program = match find_program(&programs.programs, &program) {
+                    Some(program) => program,
+                    None => return EXIT_CODE_USAGE,
+                };
+
+                let command_queue = match program.command_queues.iter().find(|command_queue| command_queue.name == queue) {
+                    Some(command_queue) => command_queue,
+                    None => {
+                        eprintln!("program `{}` does not have command queue `{}`", program.name, queue);
+                        return EXIT_CODE_USAGE;
+                    }
+                };
+
+                set_console_settings(task_manager);
+                task_manager.new_queue_if_no_running_process(program, command_queue);
+            }
+
+            run_queue(&mut library)
+        }
+        CliCommand::Download { program } => {
+            {
+                let (task_manager, programs) = library.task_manager_mut_and_programs();
+
+                let program = match find_program(&programs.programs, &program) {
+                    Some(program) => program,
+                    None => return EXIT_CODE_USAGE,
+                };
+
+                set_console_settings(task_manager);
+
+                if !task_manager.new_download_queue_if_no_running_process(program) {
+                    eprintln!("program `{}` does not have a download command", program.name);
+                    return EXIT_CODE_USAGE;
+                }
+            }
+
+            run_queue(&mut library)
+        }
+        CliCommand::Convert { format } => {
+            match library.convert_library(format) {
+                Ok(file_path) => {
+                    println!("library file converted to `{}`", file_path.display());
+                    0
+                }
+                Err(error) => {
+                    eprintln!("library conversion error: {}", error);
+                    EXIT_CODE_FAILURE
+                }
+            }
+        }
+        CliCommand::Gui { .. } | CliCommand::Help => 0,
+    }
+}
+
+fn list_programs(programs: &[Program]) {
+    for program in programs {
+        if program.download_command.is_some() {
+            println!("{} (download command) [{}]", program.name, program.source.display());
+        } else {
+            println!("{} [{}]", program.name, program.source.display());
+        }
+
+        for command_queue in &program.command_queues {
+            println!("    {}", command_queue.name);
+        }
+    }
+}
+
+/// Prints an error message if there is no program named `name`.
+fn find_program<'a>(programs: &'a [Program], name: &str) -> Option<&'a Program> {
+    let program = programs.iter().find(|program| program.name == name);
+
+    if program.is_none() {
+        eprintln!("program `{}` not found, see `program-launcher list`", name);
+    }
+
+    program
+}
+
+/// Keeps lines unchanged, so that the terminal output matches the original output.
+fn set_console_settings(task_manager: &mut TaskManager) {
+    task_manager.set_console_settings(ConsoleSettings {
+        scrollback_lines: 10000,
+        max_line_length: usize::MAX,
+        long_lines: LongLineMode::Truncate,
+    });
+
+    task_manager.collect_new_console_lines();
+}
+
+/// Runs the command queue until it finishes and prints the command output.
+/// SIGINT and SIGTERM cancel the command queue.
+///
+/// Returns exit code of the last command.
+fn run_queue(library: &mut ProgramLibraryManager) -> i32 {
+    interrupt::install_handler();
+
+    let mut interrupted = false;
+
+    loop {
+        if interrupt::take_interrupt() {
+            interrupted = true;
+            library.task_manager_mut_and_programs().0.cancel();
+        }
+
+        library.update();
+
+        let mut queue_finished = None;
+
+        while let Some(event) = library.next_event() {
+            if let Event::QueueFinished { success } = event {
+                queue_finished = Some(success);
+            }
+        }
+
+        for line in library.task_manager_mut_and_programs().0.take_new_console_lines() {
+            match line.source {
+                ConsoleLineSource::Stdout => println!("{}", line.text),
+                ConsoleLineSource::Stderr |
+                ConsoleLineSource::Launcher => eprintln!("{}", line.text),
+            }
+        }
+
+        if let Some(success) = queue_finished {
+            if interrupted {
+                return EXIT_CODE_INTERRUPTED;
+            }
+
+            return exit_code(success, library.task_manager());
+        }
+
+        thread::sleep(Duration::from_millis(UPDATE_INTERVAL_MILLISECONDS));
+    }
+}
+
+fn exit_code(success: bool, task_manager: &TaskManager) -> i32 {
+    if success {
+        return 0;
+    }
+
+    let failed_result = task_manager.command_results().iter().rev().find(|result| !result.success());
+
+    match failed_result.map(|result| &result.outcome) {
+        Some(&CommandOutcome::Exited(exit_status)) => exit_status.code().unwrap_or(EXIT_CODE_FAILURE),
+        Some(&CommandOutcome::SpawnFailed(_)) => EXIT_CODE_SPAWN_FAILED,
+        None => EXIT_CODE_FAILURE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Arguments, String> {
+        parse_arguments(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn gui_is_default_command() {
+        match parse(&[]).unwrap().command {
+            CliCommand::Gui { full_screen } => assert!(!full_screen),
+            _ => panic!("expected gui command"),
+        }
+
+        match parse(&["--fullscreen"]).unwrap().command {
+            CliCommand::Gui { full_screen } => assert!(full_screen),
+            _ => panic!("expected gui command"),
+        }
+    }
+
+    #[test]
+    fn subcommands_are_parsed() {
+        match parse(&["list"]).unwrap().command {
+            CliCommand::List => (),
+            _ => panic!("expected list command"),
+        }
+
+        match parse(&["run", "Game", "Start"]).unwrap().command {
+            CliCommand::Run { program, queue } => assert_eq!((program.as_str(), queue.as_str()), ("Game", "Start")),
+            _ => panic!("expected run command"),
+        }
+
+        match parse(&["download", "Game"]).unwrap().command {
+            CliCommand::Download { program } => assert_eq!(program, "Game"),
+            _ => panic!("expected download command"),
+        }
+
+        match parse(&["convert", "toml"]).unwrap().command {
+            CliCommand::Convert { format } => assert_eq!(format, LibraryFormat::Toml),
+            _ => panic!("expected convert command"),
+        }
+
+        for help in &["help", "--help", "-h"] {
+            match parse(&[help]).unwrap().command {
+                CliCommand::Help => (),
+                _ => panic!("expected help command"),
+            }
+        }
+    }
+
+    #[test]
+    fn library_directory_option() {
+        let arguments = parse(&["--library-dir", "a", "list"]).unwrap();
+        assert_eq!(arguments.library_directory.as_deref(), Some("a"));
+
+        let arguments = parse(&["list", "--library-dir=b"]).unwrap();
+        assert_eq!(arguments.library_directory.as_deref(), Some("b"));
+
+        assert!(parse(&["--library-dir"]).is_err());
+        assert!(parse(&["--library-dir="]).is_err());
+        assert!(parse(&["--library-dir", ""]).is_err());
+    }
+
+    #[test]
+    fn invalid_arguments_are_errors() {
+        assert!(parse(&["list", "--fullscreen"]).is_err());
+        assert!(parse(&["list", "extra"]).is_err());
+        assert!(parse(&["run", "Game", "Start", "extra"]).is_err());
+        assert!(parse(&["run", "Game"]).is_err());
+        assert!(parse(&["download"]).is_err());
+        assert!(parse(&["convert"]).is_err());
+        assert!(parse(&["convert", "yaml"]).is_err());
+        assert!(parse(&["unknown"]).is_err());
+    }
+}
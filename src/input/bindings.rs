@@ -0,0 +1,349 @@
+//! Mapping from keys and game controller inputs to input actions.
+//!
+//! Keys are identified with conrod's key names, for example `Escape`, `W` or `D1`.
+//! Game controller buttons use gilrs button names, for example `South` or `DPadUp`.
+//! Stick directions are named like `LeftStickUp` and `RightStickLeft`. Other
+//! axes, for example analog triggers, use the axis name as the input name.
+
+use conrod::input::keyboard::Key;
+
+use std::collections::{BTreeMap, HashMap};
+
+use settings::Settings;
+
+/// Game controller bindings with this name are used for controllers
+/// which don't have their own bindings.
+pub const DEFAULT_GAME_CONTROLLER: &str = "default";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Select,
+    Cancel,
+    Up,
+    Down,
+    Left,
+    Right,
+    Back,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    NextTab,
+    PreviousTab,
+}
+
+impl Action {
+    pub const ALL: &'static [Action] = &[
+        Action::Quit,
+        Action::Select,
+        Action::Cancel,
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Back,
+        Action::PageUp,
+        Action::PageDown,
+        Action::Home,
+        Action::End,
+        Action::NextTab,
+        Action::PreviousTab,
+    ];
+
+    /// Name of the action in the settings file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Select => "select",
+            Action::Cancel => "cancel",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Back => "back",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::Home => "home",
+            Action::End => "end",
+            Action::NextTab => "next_tab",
+            Action::PreviousTab => "previous_tab",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().cloned().find(|action| action.name() == name)
+    }
+}
+
+/// Name of the key in the settings file.
+pub fn key_name(key: Key) -> String {
+    format!("{:?}", key)
+}
+
+/// Replaces the first key of the action with `key`. Other keys of the action are kept.
+///
+/// Returns error if the key is already bound to another action.
+pub fn rebind_key(key_bindings: &mut BTreeMap<String, Vec<String>>, action: Action, key: &str) -> Result<(), String> {
+    let conflict = key_bindings
+        .iter()
+        .find(|&(action_name, keys)| action_name != action.name() && keys.iter().any(|bound_key| bound_key == key));
+
+    if let Some((action_name, _)) = conflict {
+        return Err(format!("key `{}` is already bound to `{}`", key, action_name));
+    }
+
+    let keys = key_bindings.entry(action.name().to_string()).or_default();
+
+    if keys.iter().any(|bound_key| bound_key == key) {
+        return Ok(());
+    }
+
+    if keys.is_empty() {
+        keys.push(key.to_string());
+    } else {
+        keys[0] = key.to_string();
+    }
+
+    Ok(())
+}
+
+/// Converts action to input name mapping from the settings file to input name to actions mapping.
+///
+/// Returns unknown action names as errors.
+fn actions_by_input(bindings: &BTreeMap<String, Vec<String>>, errors: &mut Vec<String>) -> HashMap<String, Vec<Action>> {
+    let mut actions_by_input: HashMap<String, Vec<Action>> = HashMap::new();
+
+    for (action_name, inputs) in bindings {
+        let action = match Action::from_name(action_name) {
+            Some(action) => action,
+            None => {
+                errors.push(format!("unknown input action `{}`", action_name));
+                continue;
+            }
+        };
+
+        for input in inputs {
+            actions_by_input.entry(input.clone()).or_default().push(action);
+        }
+    }
+
+    actions_by_input
+}
+
+pub struct InputBindings {
+    keys: HashMap<String, Vec<Action>>,
+    /// Bindings for each game controller name.
+    game_controllers: HashMap<String, HashMap<String, Vec<Action>>>,
+    /// Stick values between `-deadzone` and `deadzone` are ignored.
+    deadzone: f32,
+    /// Current stick directions for each controller ID and axis name.
+    stick_directions: HashMap<(usize, String), i8>,
+}
+
+impl InputBindings {
+    /// Creates bindings from settings. Unknown action names, also in key repeat
+    /// settings, are returned as errors.
+    pub fn new(settings: &Settings) -> (InputBindings, Vec<String>) {
+        let mut errors = vec![];
+
+        let keys = actions_by_input(&settings.key_bindings, &mut errors);
+
+        for action_name in settings.key_repeat.keys() {
+            if Action::from_name(action_name).is_none() {
+                errors.push(format!("unknown key repeat action `{}`", action_name));
+            }
+        }
+
+        let game_controllers = settings.game_controller_bindings
+            .iter()
+            .map(|(controller_name, bindings)| (controller_name.clone(), actions_by_input(bindings, &mut errors)))
+            .collect();
+
+        let bindings = InputBindings {
+            keys,
+            game_controllers,
+            deadzone: settings.game_controller_deadzone,
+            stick_directions: HashMap::new(),
+        };
+
+        (bindings, errors)
+    }
+
+    pub fn key_actions(&self, key: Key) -> &[Action] {
+        match self.keys.get(&key_name(key)) {
+            Some(actions) => actions,
+            None => &[],
+        }
+    }
+
+    /// Actions for game controller button or stick direction.
+    pub fn game_controller_actions(&self, controller_name: &str, input: &str) -> &[Action] {
+        let bindings = self.game_controllers
+            .get(controller_name)
+            .or_else(|| self.game_controllers.get(DEFAULT_GAME_CONTROLLER));
+
+        match bindings.and_then(|bindings| bindings.get(input)) {
+            Some(actions) => actions,
+            None => &[],
+        }
+    }
+
+    /// Updates stick direction from an axis value and returns changed stick
+    /// directions as (input name, pressed) pairs.
+    ///
+    /// Axes which names end with `X` or `Y`, for example `LeftStickX`, are sticks and
+    /// positive values are right and up. Other axes only have the positive direction.
+    pub fn stick_direction_changes(&mut self, controller_id: usize, axis: &str, value: f32) -> Vec<(String, bool)> {
+        let (negative, positive) = stick_direction_names(axis);
+
+        let new_direction = if value >= self.deadzone {
+            1
+        } else if value <= -self.deadzone {
+            -1
+        } else {
+            0
+        };
+
+        let direction = self.stick_directions.entry((controller_id, axis.to_string())).or_insert(0);
+
+        let mut changes = vec![];
+
+        if *direction == new_direction {
+            return changes;
+        }
+
+        match *direction {
+            1 => changes.push((positive.clone(), false)),
+            -1 => changes.extend(negative.clone().map(|negative| (negative, false))),
+            _ => (),
+        }
+
+        match new_direction {
+            1 => changes.push((positive, true)),
+            -1 => changes.extend(negative.map(|negative| (negative, true))),
+            _ => (),
+        }
+
+        *direction = new_direction;
+
+        changes
+    }
+}
+
+/// Returns (negative, positive) direction names for an axis.
+fn stick_direction_names(axis: &str) -> (Option<String>, String) {
+    if let Some(stick) = axis.strip_suffix('X') {
+        (Some(format!("{}Left", stick)), format!("{}Right", stick))
+    } else if let Some(stick) = axis.strip_suffix('Y') {
+        (Some(format!("{}Down", stick)), format!("{}Up", stick))
+    } else {
+        (None, axis.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_bindings(bindings: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        bindings.iter().map(|&(action, keys)| {
+            (action.to_string(), keys.iter().map(|key| key.to_string()).collect())
+        }).collect()
+    }
+
+    fn input_bindings() -> InputBindings {
+        let mut game_controller_bindings = BTreeMap::new();
+        game_controller_bindings.insert(DEFAULT_GAME_CONTROLLER.to_string(), key_bindings(&[("select", &["South"])]));
+        game_controller_bindings.insert("Pad".to_string(), key_bindings(&[("back", &["South"])]));
+
+        let settings = Settings {
+            key_bindings: key_bindings(&[("up", &["Up", "W"]), ("select", &["Return"]), ("page_up", &["Up"])]),
+            game_controller_deadzone: 0.5,
+            game_controller_bindings,
+            ..Settings::default()
+        };
+
+        let (bindings, errors) = InputBindings::new(&settings);
+        assert!(errors.is_empty());
+
+        bindings
+    }
+
+    #[test]
+    fn actions_are_mapped_by_input() {
+        let mut errors = vec![];
+        let bindings = key_bindings(&[("up", &["Up", "W"]), ("page_up", &["Up"]), ("jump", &["Space"])]);
+
+        let actions = actions_by_input(&bindings, &mut errors);
+
+        assert_eq!(actions["Up"], vec![Action::PageUp, Action::Up]);
+        assert_eq!(actions["W"], vec![Action::Up]);
+        assert!(!actions.contains_key("Space"));
+        assert_eq!(errors, vec!["unknown input action `jump`"]);
+    }
+
+    #[test]
+    fn unknown_key_repeat_action_is_error() {
+        let mut settings = Settings::default();
+        settings.key_repeat.insert("jump".to_string(), Default::default());
+
+        let (_, errors) = InputBindings::new(&settings);
+
+        assert_eq!(errors, vec!["unknown key repeat action `jump`"]);
+    }
+
+    #[test]
+    fn controller_bindings_fall_back_to_default() {
+        let bindings = input_bindings();
+
+        assert_eq!(bindings.game_controller_actions("Pad", "South"), &[Action::Back]);
+        assert_eq!(bindings.game_controller_actions("Other", "South"), &[Action::Select]);
+        assert!(bindings.game_controller_actions("Other", "North").is_empty());
+    }
+
+    #[test]
+    fn stick_direction_names_for_axes() {
+        assert_eq!(stick_direction_names("LeftStickX"), (Some("LeftStickLeft".to_string()), "LeftStickRight".to_string()));
+        assert_eq!(stick_direction_names("RightStickY"), (Some("RightStickDown".to_string()), "RightStickUp".to_string()));
+        assert_eq!(stick_direction_names("LeftZ"), (None, "LeftZ".to_string()));
+    }
+
+    #[test]
+    fn stick_directions_change_outside_deadzone() {
+        let mut bindings = input_bindings();
+        let changes = |bindings: &mut InputBindings, axis, value| bindings.stick_direction_changes(0, axis, value);
+
+        assert!(changes(&mut bindings, "LeftStickY", 0.4).is_empty());
+        assert_eq!(changes(&mut bindings, "LeftStickY", 0.6), vec![("LeftStickUp".to_string(), true)]);
+        assert!(changes(&mut bindings, "LeftStickY", 0.9).is_empty());
+        assert_eq!(changes(&mut bindings, "LeftStickY", -0.6), vec![("LeftStickUp".to_string(), false), ("LeftStickDown".to_string(), true)]);
+        assert_eq!(changes(&mut bindings, "LeftStickY", 0.0), vec![("LeftStickDown".to_string(), false)]);
+
+        // Axes without a negative direction only release the positive direction.
+        assert_eq!(changes(&mut bindings, "LeftZ", 0.6), vec![("LeftZ".to_string(), true)]);
+        assert_eq!(changes(&mut bindings, "LeftZ", -0.6), vec![("LeftZ".to_string(), false)]);
+
+        // Each controller has its own stick directions.
+        assert_eq!(bindings.stick_direction_changes(1, "LeftStickY", 0.6), vec![("LeftStickUp".to_string(), true)]);
+    }
+
+    #[test]
+    fn rebind_replaces_first_key() {
+        let mut bindings = key_bindings(&[("up", &["Up", "W"]), ("down", &["Down"])]);
+
+        rebind_key(&mut bindings, Action::Up, "K").unwrap();
+        rebind_key(&mut bindings, Action::Home, "Home").unwrap();
+
+        assert_eq!(bindings, key_bindings(&[("up", &["K", "W"]), ("down", &["Down"]), ("home", &["Home"])]));
+    }
+
+    #[test]
+    fn rebind_conflict_is_error() {
+        let mut bindings = key_bindings(&[("up", &["Up", "W"]), ("down", &["Down"])]);
+
+        assert!(rebind_key(&mut bindings, Action::Down, "W").is_err());
+        assert!(rebind_key(&mut bindings, Action::Up, "W").is_ok());
+        assert_eq!(bindings, key_bindings(&[("up", &["Up", "W"]), ("down", &["Down"])]));
+    }
+}
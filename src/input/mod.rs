@@ -0,0 +1,520 @@
+
+
+pub mod bindings;
+
+use conrod::input::keyboard::Key;
+
+use input::utils::{KeyHitGenerator, KeyEvent};
+use input::bindings::{Action, InputBindings};
+
+use settings::Settings;
+
+use utils::TimeMilliseconds;
+
+use std::mem;
+
+pub struct InputManager {
+    quit: bool,
+    right: KeyHitGenerator,
+    left: KeyHitGenerator,
+    up: KeyHitGenerator,
+    down: KeyHitGenerator,
+    select: bool,
+    cancel: bool,
+    back: bool,
+    page_up: KeyHitGenerator,
+    page_down: KeyHitGenerator,
+    home: bool,
+    end: bool,
+    next_tab: bool,
+    previous_tab: bool,
+    /// Text field has keyboard focus.
+    text_input: bool,
+    bindings: InputBindings,
+}
+
+impl InputManager {
+    pub fn new(settings: &Settings) -> InputManager {
+        let mut input_manager = InputManager {
+            quit: false,
+            right: KeyHitGenerator::new(),
+            left: KeyHitGenerator::new(),
+            up: KeyHitGenerator::new(),
+            down: KeyHitGenerator::new(),
+            select: false,
+            cancel: false,
+            back: false,
+            page_up: KeyHitGenerator::new(),
+            page_down: KeyHitGenerator::new(),
+            home: false,
+            end: false,
+            next_tab: false,
+            previous_tab: false,
+            text_input: false,
+            bindings: create_bindings(settings),
+        };
+
+        input_manager.set_key_repeat(settings);
+        input_manager
+    }
+
+    /// Updates input bindings and key repeat timing from settings.
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.bindings = create_bindings(settings);
+        self.set_key_repeat(settings);
+    }
+
+    fn set_key_repeat(&mut self, settings: &Settings) {
+        let key_repeat = |action: Action| {
+            settings.key_repeat.get(action.name()).cloned().unwrap_or_default()
+        };
+
+        self.up.set_settings(key_repeat(Action::Up));
+        self.down.set_settings(key_repeat(Action::Down));
+        self.left.set_settings(key_repeat(Action::Left));
+        self.right.set_settings(key_repeat(Action::Right));
+        self.page_up.set_settings(key_repeat(Action::PageUp));
+        self.page_down.set_settings(key_repeat(Action::PageDown));
+    }
+
+    /// When text input is enabled, keyboard is only used for the text input.
+    pub fn set_text_input(&mut self, value: bool) {
+        self.text_input = value;
+    }
+
+    pub fn update(&mut self, current_time: &TimeMilliseconds) {
+        self.up.update(current_time, true);
+        self.down.update(current_time, true);
+        self.left.update(current_time, true);
+        self.right.update(current_time, true);
+        self.page_up.update(current_time, true);
+        self.page_down.update(current_time, true);
+    }
+
+    fn update_action(&mut self, action: Action, pressed: bool, current_time: &TimeMilliseconds) {
+        let key_event = if pressed {
+            KeyEvent::KeyDown
+        } else {
+            KeyEvent::KeyUp
+        };
+
+        match action {
+            Action::Quit => if pressed { self.quit = true },
+            Action::Select => if !pressed { self.select = true },
+            Action::Cancel => if !pressed { self.cancel = true },
+            Action::Up => self.update_up(key_event, current_time),
+            Action::Down => self.update_down(key_event, current_time),
+            Action::Left => self.update_left(key_event, current_time),
+            Action::Right => self.update_right(key_event, current_time),
+            Action::Back => if !pressed { self.back = true },
+            Action::PageUp => self.update_page_up(key_event, current_time),
+            Action::PageDown => self.update_page_down(key_event, current_time),
+            Action::Home => if !pressed { self.home = true },
+            Action::End => if !pressed { self.end = true },
+            Action::NextTab => if !pressed { self.next_tab = true },
+            Action::PreviousTab => if !pressed { self.previous_tab = true },
+        }
+    }
+
+    /// Returns true if there was actions for the input.
+    fn update_actions(&mut self, actions: Vec<Action>, pressed: bool, current_time: &TimeMilliseconds) -> bool {
+        for &action in &actions {
+            self.update_action(action, pressed, current_time);
+        }
+
+        !actions.is_empty()
+    }
+}
+
+fn create_bindings(settings: &Settings) -> InputBindings {
+    let (bindings, errors) = InputBindings::new(settings);
+
+    for error in errors {
+        println!("input bindings error: {}", error);
+    }
+
+    bindings
+}
+
+pub trait InputUpdater {
+    fn set_quit(&mut self, value: bool);
+    fn update_right(&mut self, key_event: KeyEvent, current_time: &TimeMilliseconds);
+    fn update_left(&mut self, key_event: KeyEvent, current_time: &TimeMilliseconds);
+    fn update_up(&mut self, key_event: KeyEvent, current_time: &TimeMilliseconds);
+    fn update_down(&mut self, key_event: KeyEvent, current_time: &TimeMilliseconds);
+    fn set_select(&mut self, value: bool);
+    fn set_cancel(&mut self, value: bool);
+    fn set_back(&mut self, value: bool);
+    fn update_page_up(&mut self, key_event: KeyEvent, current_time: &TimeMilliseconds);
+    fn update_page_down(&mut self, key_event: KeyEvent, current_time: &TimeMilliseconds);
+    fn set_home(&mut self, value: bool);
+    fn set_end(&mut self, value: bool);
+    fn set_next_tab(&mut self, value: bool);
+    fn set_previous_tab(&mut self, value: bool);
+
+    /// Updates actions bound to the key. Keys are ignored when
+    /// keyboard is used for text input.
+    ///
+    /// Returns true if InputUpdater was updated.
+    fn key_input(&mut self, key: Key, pressed: bool, current_time: &TimeMilliseconds) -> bool;
+
+    /// Updates actions bound to the game controller button.
+    /// Button names are gilrs button names.
+    ///
+    /// Returns true if InputUpdater was updated.
+    fn game_controller_button(&mut self, controller_name: &str, button: &str, pressed: bool, current_time: &TimeMilliseconds) -> bool;
+
+    /// Updates actions bound to stick directions. Axis names are gilrs axis names
+    /// and axis value is in range [-1.0, 1.0], where positive values are right and up.
+    ///
+    /// Returns true if InputUpdater was updated.
+    fn game_controller_axis(&mut self, controller_id: usize, controller_name: &str, axis: &str, value: f32, current_time: &TimeMilliseconds) -> bool;
+}
+
+
+pub trait Input {
+    fn quit(&self) -> bool;
+    fn right(&mut self) -> bool;
+    fn left(&mut self) -> bool;
+    fn up(&mut self) -> bool;
+    fn down(&mut self) -> bool;
+    fn select(&mut self) -> bool;
+    fn cancel(&mut self) -> bool;
+    /// Go back to the previous view.
+    fn back(&mut self) -> bool;
+    fn page_up(&mut self) -> bool;
+    fn page_down(&mut self) -> bool;
+    fn home(&mut self) -> bool;
+    fn end(&mut self) -> bool;
+    fn next_tab(&mut self) -> bool;
+    fn previous_tab(&mut self) -> bool;
+}
+
+impl InputUpdater for InputManager {
+    fn set_quit(&mut self, value: bool) {
+        self.quit = value;
+    }
+    fn set_select(&mut self, value: bool) {
+        self.select = value;
+    }
+    fn set_cancel(&mut self, value: bool) {
+        self.cancel = value;
+    }
+    fn set_back(&mut self, value: bool) {
+        self.back = value;
+    }
+    fn set_home(&mut self, value: bool) {
+        self.home = value;
+    }
+    fn set_end(&mut self, value: bool) {
+        self.end = value;
+    }
+    fn set_next_tab(&mut self, value: bool) {
+        self.next_tab = value;
+    }
+    fn set_previous_tab(&mut self, value: bool) {
+        self.previous_tab = value;
+    }
+    fn key_input(&mut self, key: Key, pressed: bool, current_time: &TimeMilliseconds) -> bool {
+        if self.text_input {
+            return false;
+        }
+
+        let actions = self.bindings.key_actions(key).to_vec();
+        self.update_actions(actions, pressed, current_time)
+    }
+    fn game_controller_button(&mut self, controller_name: &str, button: &str, pressed: bool, current_time: &TimeMilliseconds) -> bool {
+        let actions = self.bindings.game_controller_actions(controller_name, button).to_vec();
+        self.update_actions(actions, pressed, current_time)
+    }
+    fn game_controller_axis(&mut self, controller_id: usize, controller_name: &str, axis: &str, value: f32, current_time: &TimeMilliseconds) -> bool {
+        let mut updated = false;
+
+        for (direction, pressed) in self.bindings.stick_direction_changes(controller_id, axis, value) {
+            if self.game_controller_button(controller_name, &direction, pressed, current_time) {
+                updated = true;
+            }
+        }
+
+        updated
+    }
+    fn update_right(&mut self, key_event: KeyEvent, current_time: &TimeMilliseconds) { self.right.update_from_key_event(key_event, current_time) }
+    fn update_left(&mut self, key_event: KeyEvent, current_time: &TimeMilliseconds) { self.left.update_from_key_event(key_event, current_time) }
+    fn update_up(&mut self, key_event: KeyEvent, current_time: &TimeMilliseconds) { self.up.update_from_key_event(key_event, current_time) }
+    fn update_down(&mut self, key_event: KeyEvent, current_time: &TimeMilliseconds) { self.down.update_from_key_event(key_event, current_time) }
+    fn update_page_up(&mut self, key_event: KeyEvent, current_time: &TimeMilliseconds) { self.page_up.update_from_key_event(key_event, current_time) }
+    fn update_page_down(&mut self, key_event: KeyEvent, current_time: &TimeMilliseconds) { self.page_down.update_from_key_event(key_event, current_time) }
+}
+
+impl Input for InputManager {
+    fn quit(&self) -> bool { self.quit }
+    fn right(&mut self) -> bool { self.right.key_hit() }
+    fn left(&mut self) -> bool { self.left.key_hit() }
+    fn up(&mut self) -> bool { self.up.key_hit() }
+    fn down(&mut self) -> bool { self.down.key_hit() }
+    fn select(&mut self) -> bool {
+        if self.select {
+            self.select = false;
+            true
+        } else {
+            false
+        }
+    }
+    fn cancel(&mut self) -> bool {
+        if self.cancel {
+            self.cancel = false;
+            true
+        } else {
+            false
+        }
+    }
+    fn back(&mut self) -> bool { mem::replace(&mut self.back, false) }
+    fn page_up(&mut self) -> bool { self.page_up.key_hit() }
+    fn page_down(&mut self) -> bool { self.page_down.key_hit() }
+    fn home(&mut self) -> bool { mem::replace(&mut self.home, false) }
+    fn end(&mut self) -> bool { mem::replace(&mut self.end, false) }
+    fn next_tab(&mut self) -> bool { mem::replace(&mut self.next_tab, false) }
+    fn previous_tab(&mut self) -> bool { mem::replace(&mut self.previous_tab, false) }
+}
+
+
+
+
+pub mod utils {
+
+    //! Utilities for `input` module's objects.
+
+    use utils::{Timer, TimeMilliseconds};
+
+    /// Key press states.
+    #[derive(Clone)]
+    pub enum KeyEvent {
+        KeyUp,
+        KeyDown,
+    }
+
+    /// KeyHitGenerator's states.
+    enum KeyHitState {
+        /// Normal key hits.
+        NormalMode,
+        /// Generator generated key hits.
+        ScrollMode,
+    }
+
+    /// Key repeat timing of `KeyHitGenerator`.
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct KeyRepeatSettings {
+        /// Time from key press to the first generated key hit.
+        pub initial_delay_milliseconds: u32,
+        /// Time between generated key hits.
+        pub repeat_interval_milliseconds: u32,
+        /// Repeat interval is divided by this after every generated key hit.
+        /// Value `1.0` disables acceleration.
+        pub acceleration: f32,
+        /// Acceleration will not make repeat interval shorter than this.
+        pub min_repeat_interval_milliseconds: u32,
+    }
+
+    impl Default for KeyRepeatSettings {
+        fn default() -> KeyRepeatSettings {
+            KeyRepeatSettings {
+                initial_delay_milliseconds: 300,
+                repeat_interval_milliseconds: 100,
+                acceleration: 1.0,
+                min_repeat_interval_milliseconds: 20,
+            }
+        }
+    }
+
+    /// Generate key hits.
+    ///
+    /// Generates key hits from key up event and if the key is pressed down
+    /// long enough, the generator will generate multiple key hits.
+    pub struct KeyHitGenerator {
+        settings: KeyRepeatSettings,
+        /// Current time between generated key hits.
+        repeat_interval: f32,
+        timer: Timer,
+        state: Option<KeyHitState>,
+        key_hit: bool,
+    }
+
+    impl KeyHitGenerator {
+        /// Create new `KeyHitGenerator` with default `KeyRepeatSettings`.
+        pub fn new() -> KeyHitGenerator {
+            KeyHitGenerator::with_settings(KeyRepeatSettings::default())
+        }
+
+        pub fn with_settings(settings: KeyRepeatSettings) -> KeyHitGenerator {
+            KeyHitGenerator {
+                settings,
+                repeat_interval: settings.repeat_interval_milliseconds as f32,
+                timer: Timer::new(),
+                state: None,
+                key_hit: false,
+            }
+        }
+
+        /// Changes key repeat timing.
+        pub fn set_settings(&mut self, settings: KeyRepeatSettings) {
+            self.settings = settings;
+        }
+
+        /// Updates generators state from `KeyEvent`.
+        pub fn update_from_key_event(&mut self, key_event: KeyEvent, current_time: &TimeMilliseconds) {
+            match key_event {
+                KeyEvent::KeyUp => self.up(),
+                KeyEvent::KeyDown => self.down(current_time),
+            }
+        }
+
+        /// Update method will generate key hits if
+        ///
+        /// * There is enough time passed from the last key hit.
+        /// * `key_down` argument is true.
+        pub fn update(&mut self, current_time: &TimeMilliseconds, key_down: bool) {
+            // TODO: this can be removed?
+            if !key_down {
+                return;
+            }
+
+            match self.state {
+                Some(KeyHitState::NormalMode) => {
+                    if self.timer.check(current_time, self.settings.initial_delay_milliseconds) {
+                        self.state = Some(KeyHitState::ScrollMode);
+                        self.repeat_interval = self.settings.repeat_interval_milliseconds as f32;
+                        self.key_hit = true;
+                    }
+                },
+                Some(KeyHitState::ScrollMode) => {
+                    if self.timer.check(current_time, self.repeat_interval as u32) {
+                        self.key_hit = true;
+
+                        let min_interval = self.settings.min_repeat_interval_milliseconds as f32;
+                        let accelerated = self.repeat_interval / self.settings.acceleration.max(1.0);
+                        self.repeat_interval = accelerated.max(min_interval).min(self.repeat_interval);
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        /// Handle key down event.
+        ///
+        /// Sets generators state to `Some(KeyHitState::NormalMode)` and resets
+        /// generators internal timer if generators current state is `None`.
+        fn down(&mut self, current_time: &TimeMilliseconds) {
+            match self.state {
+                None => {
+                    self.state = Some(KeyHitState::NormalMode);
+                    self.timer.reset(current_time);
+                },
+                _ => (),
+            }
+        }
+
+        /// Handle key up event.
+        ///
+        /// Creates a key hit if method is called when
+        /// generators state is `Some(KeyHitState::NormalMode)`.
+        ///
+        /// Generators state will be set to `None`.
+        fn up(&mut self) {
+            if let Some(KeyHitState::NormalMode) = self.state {
+                self.key_hit = true;
+            } else {
+                self.key_hit = false;
+            };
+
+            self.state = None;
+        }
+
+        /// Returns true if key hit has been happened.
+        ///
+        /// This method will also clear the current key hit.
+        pub fn key_hit(&mut self) -> bool {
+            if self.key_hit {
+                self.clear();
+                true
+            } else {
+                false
+            }
+        }
+
+        /// Clears current key hit.
+        pub fn clear(&mut self) {
+            self.key_hit = false;
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn time(milliseconds: u32) -> TimeMilliseconds {
+            TimeMilliseconds::from_milliseconds(milliseconds)
+        }
+
+        fn generator() -> KeyHitGenerator {
+            KeyHitGenerator::with_settings(KeyRepeatSettings {
+                initial_delay_milliseconds: 300,
+                repeat_interval_milliseconds: 100,
+                acceleration: 2.0,
+                min_repeat_interval_milliseconds: 30,
+            })
+        }
+
+        #[test]
+        fn short_key_press_is_one_key_hit() {
+            let mut generator = generator();
+
+            generator.update_from_key_event(KeyEvent::KeyDown, &time(0));
+            generator.update(&time(299), true);
+            assert!(!generator.key_hit());
+
+            generator.update_from_key_event(KeyEvent::KeyUp, &time(299));
+            assert!(generator.key_hit());
+            assert!(!generator.key_hit());
+        }
+
+        #[test]
+        fn key_hits_are_repeated_with_acceleration() {
+            let mut generator = generator();
+            generator.update_from_key_event(KeyEvent::KeyDown, &time(1000));
+
+            let mut hit_times = vec![];
+
+            for current_time in 1000..1600 {
+                generator.update(&time(current_time), true);
+
+                if generator.key_hit() {
+                    hit_times.push(current_time);
+                }
+            }
+
+            // Initial delay, then intervals 100, 50 and min interval 30.
+            assert_eq!(hit_times[..5], [1300, 1400, 1450, 1480, 1510]);
+
+            // Releasing the key after repeated key hits is not a key hit.
+            generator.update_from_key_event(KeyEvent::KeyUp, &time(1600));
+            generator.update(&time(2000), true);
+            assert!(!generator.key_hit());
+        }
+
+        #[test]
+        fn settings_change_timing() {
+            let mut generator = generator();
+            generator.set_settings(KeyRepeatSettings {
+                initial_delay_milliseconds: 50,
+                ..KeyRepeatSettings::default()
+            });
+
+            generator.update_from_key_event(KeyEvent::KeyDown, &time(0));
+            generator.update(&time(49), true);
+            assert!(!generator.key_hit());
+            generator.update(&time(50), true);
+            assert!(generator.key_hit());
+        }
+    }
+}
@@ -10,11 +10,19 @@ extern crate sdl2;
 
 extern crate backend_library;
 
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
 pub mod input;
 pub mod window;
 pub mod ui;
 pub mod renderer;
 pub mod utils;
+pub mod settings;
+pub mod cli;
+pub mod paths;
 
 use renderer::{Renderer, OpenGLRenderer};
 
@@ -23,32 +31,91 @@ use input::{Input, InputManager};
 
 use utils::{FpsCounter, TimeManager};
 
+use settings::Settings;
+
+use std::path::Path;
+
 const DEFAULT_WINDOW_WIDTH: u32 = 640;
 const DEFAULT_WINDOW_HEIGHT: u32 = 480;
 
-const LIBRARY_DIRECTORY_NAME: &'static str = "program_launcher_library";
+use backend_library::ProgramLibraryManager;
+use backend_library::task_manager::TaskManager;
 
-use backend_library::{ProgramLibraryManager, Event};
 
 
+/// Sets console scrollback from settings.
+fn apply_console_settings(task_manager: &mut TaskManager, settings: &Settings) {
+    let mut console_settings = task_manager.console_settings();
+    console_settings.scrollback_lines = settings.console_scrollback_lines;
+    task_manager.set_console_settings(console_settings);
+}
+
+/// Settings are not saved if `settings_file_path` is None.
+fn save_settings(settings: &Settings, settings_file_path: Option<&Path>) {
+    if let Some(settings_file_path) = settings_file_path {
+        if let Err(error) = settings.save(settings_file_path) {
+            eprintln!("settings saving error: {:?}", error);
+        }
+    }
+}
 
 fn main() {
 
-    let mut full_screen = false;
+    let settings_file_path = paths::settings_file_path();
+
+    // Settings file which can't be loaded is backed up before default
+    // settings are saved over it. If backup fails, settings are not saved.
+    let (settings, settings_file_path) = match Settings::load(&settings_file_path) {
+        Ok(settings) => (settings, Some(settings_file_path)),
+        Err(error) => {
+            eprintln!("settings loading error: {:?}", error);
+
+            match settings::backup_settings_file(&settings_file_path) {
+                Ok(backup_file_path) => {
+                    eprintln!("settings file was copied to `{}`", backup_file_path.display());
+                    (Settings::default(), Some(settings_file_path))
+                }
+                Err(error) => {
+                    eprintln!("settings file backup error: {:?}, settings will not be saved", error);
+                    (Settings::default(), None)
+                }
+            }
+        }
+    };
 
-    for arg in std::env::args().skip(1) {
-        if arg == "--fullscreen" {
-            full_screen = true;
+    let arguments = match cli::parse_arguments(std::env::args().skip(1)) {
+        Ok(arguments) => arguments,
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, cli::USAGE);
+            std::process::exit(cli::EXIT_CODE_USAGE);
         }
+    };
+
+    let library_directory = paths::library_directory(arguments.library_directory.as_ref().map(|s| s.as_str()), &settings.library_directory);
+
+    let full_screen = match arguments.command {
+        cli::CliCommand::Gui { full_screen } => settings.full_screen || full_screen,
+        command => std::process::exit(cli::run(command, &library_directory)),
+    };
+
+    let mut library = ProgramLibraryManager::new(&library_directory);
+
+    apply_console_settings(library.task_manager_mut_and_programs().0, &settings);
+
+    for diagnostic in library.library_diagnostics() {
+        eprintln!("{}", diagnostic);
     }
 
-    let mut library = ProgramLibraryManager::new(LIBRARY_DIRECTORY_NAME).expect("library loading error");
+    // Glutin window is used if both window features are enabled.
 
     #[cfg(feature = "glutin-window")]
-    let mut window = window::glutin::GlutinWindow::new("Program launcher", DEFAULT_WINDOW_WIDTH, DEFAULT_WINDOW_HEIGHT, full_screen);
+    let mut window = window::glutin::GlutinWindow::new("Program launcher", settings.window_width, settings.window_height, full_screen, settings.full_screen_monitor);
 
-    let mut input = InputManager::new();
-    let mut ui = ui::UiManager::new();
+    #[cfg(all(feature = "sdl2-window", not(feature = "glutin-window")))]
+    let mut window = window::sdl2::SDL2Window::new("Program launcher", settings.window_width, settings.window_height, full_screen, settings.full_screen_monitor);
+
+    let mut input = InputManager::new(&settings);
+    let mut ui = ui::UiManager::new(settings);
 
     let mut renderer = OpenGLRenderer::new(&window);
 
@@ -58,30 +125,54 @@ fn main() {
     loop {
         time_manager.update_time(false);
 
-        let mut console_text_update = false;
-        {
-            if let Some(Event::ConsoleUpdate(console_lines)) = library.update() {
-                console_text_update = true;
-                ui.update_console_text(console_lines);
-            }
+        let mut backend_update = false;
+
+        library.update();
+
+        while let Some(event) = library.next_event() {
+            ui.backend_event(&event, library.programs());
+            backend_update = true;
         }
 
         let mut update_ui = false;
-        let (task_manager, programs) = library.task_manager_mut_and_programs();
 
-        if window.update_input(&mut input, ui.ui_mut(), time_manager.current_time()) || console_text_update {
+        input.set_text_input(ui.text_field_focused());
+
+        if window.update_input(&mut input, ui.ui_mut(), time_manager.current_time()) || backend_update {
             update_ui = true;
         }
 
-        if ui.input_update(&mut input, programs) {
+        if ui.input_update(&mut input, library.programs()) {
             update_ui = true;
         }
 
         if update_ui {
-            ui.set_widgets(task_manager, programs, &mut window);
+            ui.set_widgets(&mut library, &mut window);
+
+            if ui.take_settings_changed() {
+                input.apply_settings(ui.settings());
+                apply_console_settings(library.task_manager_mut_and_programs().0, ui.settings());
+                save_settings(ui.settings(), settings_file_path.as_deref());
+            }
         }
 
         if input.quit() {
+            if !window.full_screen() {
+                // Save logical size, so that the window size is the same
+                // after restart on HiDPI displays.
+                let (width, height) = window.width_and_height();
+                let dpi_factor = window.dpi_factor();
+                let width = (f64::from(width) / dpi_factor).round() as u32;
+                let height = (f64::from(height) / dpi_factor).round() as u32;
+                let mut settings = ui.settings().clone();
+
+                if settings.window_width != width || settings.window_height != height {
+                    settings.window_width = width;
+                    settings.window_height = height;
+                    save_settings(&settings, settings_file_path.as_deref());
+                }
+            }
+
             break;
         }
 
@@ -0,0 +1,91 @@
+//! Default locations of the library directory and the settings file.
+//!
+//! Locations follow the XDG Base Directory Specification.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use settings::SETTINGS_FILE_NAME;
+
+/// Environment variable for the library directory.
+pub const LIBRARY_DIRECTORY_ENV: &str = "PROGRAM_LAUNCHER_LIBRARY";
+
+/// Settings file in the current directory which was used before the config directory.
+const OLD_SETTINGS_FILE_NAME: &str = "program_launcher_settings.json";
+
+/// Library directory in the current directory which was used before the XDG data directory.
+const OLD_LIBRARY_DIRECTORY_NAME: &str = "program_launcher_library";
+
+/// Directory name in XDG data and config directories.
+const APPLICATION_DIRECTORY_NAME: &str = "program-launcher";
+
+/// Returns directory from environment variable `xdg_variable` if it is set to an absolute
+/// path. Otherwise returns `default_path` relative to the home directory.
+fn xdg_directory(xdg_variable: &str, default_path: &str) -> Option<PathBuf> {
+    if let Some(path) = env::var_os(xdg_variable).map(PathBuf::from) {
+        if path.is_absolute() {
+            return Some(path);
+        }
+    }
+
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(default_path))
+}
+
+/// `$XDG_DATA_HOME/program-launcher`
+pub fn default_library_directory() -> PathBuf {
+    match xdg_directory("XDG_DATA_HOME", ".local/share") {
+        Some(path) => path.join(APPLICATION_DIRECTORY_NAME),
+        None => PathBuf::from(APPLICATION_DIRECTORY_NAME),
+    }
+}
+
+/// `$XDG_CONFIG_HOME/program-launcher`
+pub fn config_directory() -> PathBuf {
+    match xdg_directory("XDG_CONFIG_HOME", ".config") {
+        Some(path) => path.join(APPLICATION_DIRECTORY_NAME),
+        None => PathBuf::from(APPLICATION_DIRECTORY_NAME),
+    }
+}
+
+/// Returns the old location in the current directory if it exists and the new location
+/// does not exist. Prints a warning to stderr if the old location is returned.
+fn old_location_if_new_does_not_exist(new_path: PathBuf, old_path: &str) -> PathBuf {
+    let old_path = Path::new(old_path);
+
+    if new_path.exists() || !old_path.exists() {
+        return new_path;
+    }
+
+    eprintln!("warning: using `{}` from the current directory, move it to `{}` to use the new location", old_path.display(), new_path.display());
+
+    old_path.to_path_buf()
+}
+
+/// `$XDG_CONFIG_HOME/program-launcher/settings.json`, or `program_launcher_settings.json`
+/// in the current directory if only it exists.
+pub fn settings_file_path() -> PathBuf {
+    old_location_if_new_does_not_exist(config_directory().join(SETTINGS_FILE_NAME), OLD_SETTINGS_FILE_NAME)
+}
+
+/// Selects the library directory. Directory from the command line is used first,
+/// then the environment variable, then the settings file and then the default directory.
+///
+/// Directory `program_launcher_library` in the current directory is used instead of the
+/// default directory if only it exists.
+pub fn library_directory(command_line: Option<&str>, settings: &str) -> PathBuf {
+    if let Some(directory) = command_line {
+        return PathBuf::from(directory);
+    }
+
+    if let Some(directory) = env::var_os(LIBRARY_DIRECTORY_ENV) {
+        if !directory.is_empty() {
+            return PathBuf::from(directory);
+        }
+    }
+
+    if !settings.is_empty() {
+        return PathBuf::from(settings);
+    }
+
+    old_location_if_new_does_not_exist(default_library_directory(), OLD_LIBRARY_DIRECTORY_NAME)
+}
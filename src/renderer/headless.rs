@@ -0,0 +1,138 @@
+//! Renderer which records conrod primitives instead of drawing them.
+
+use conrod::render::PrimitiveKind;
+use conrod::{Color, Rect, widget};
+
+use std::fmt::Write;
+
+use window::headless::HeadlessWindow;
+
+use ui::UiManager;
+
+use super::Renderer;
+
+/// Simplified conrod primitive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedPrimitive {
+    pub widget_id: widget::Id,
+    pub kind: RecordedKind,
+    pub rect: Rect,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordedKind {
+    Rectangle(Color),
+    Triangles,
+    Image,
+    Text(Color),
+    Other,
+}
+
+pub struct HeadlessRenderer {
+    primitives: Vec<RecordedPrimitive>,
+    /// Count of frames where the UI was changed.
+    frame_count: usize,
+}
+
+impl HeadlessRenderer {
+    pub fn new() -> HeadlessRenderer {
+        HeadlessRenderer {
+            primitives: vec![],
+            frame_count: 0,
+        }
+    }
+
+    /// Primitives of the latest changed frame.
+    pub fn primitives(&self) -> &[RecordedPrimitive] {
+        &self.primitives
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Text representation of the latest changed frame, one primitive per line.
+    pub fn snapshot(&self) -> String {
+        let mut snapshot = String::new();
+
+        for primitive in &self.primitives {
+            let (x, y) = (primitive.rect.x(), primitive.rect.y());
+            let (w, h) = (primitive.rect.w(), primitive.rect.h());
+            writeln!(snapshot, "{:?} {:?} x: {:.1}, y: {:.1}, w: {:.1}, h: {:.1}", primitive.widget_id, primitive.kind, x, y, w, h).unwrap();
+        }
+
+        snapshot
+    }
+}
+
+impl Renderer<HeadlessWindow> for HeadlessRenderer {
+    fn render(&mut self, _window: &mut HeadlessWindow, ui: &mut UiManager) {
+        let mut primitives = match ui.ui_mut().draw_if_changed() {
+            Some(primitives) => primitives,
+            None => return,
+        };
+
+        self.primitives.clear();
+        self.frame_count += 1;
+
+        while let Some(primitive) = primitives.next() {
+            let kind = match primitive.kind {
+                PrimitiveKind::Rectangle { color } => RecordedKind::Rectangle(color),
+                PrimitiveKind::TrianglesSingleColor { .. } |
+                PrimitiveKind::TrianglesMultiColor { .. } => RecordedKind::Triangles,
+                PrimitiveKind::Image { .. } => RecordedKind::Image,
+                PrimitiveKind::Text { color, .. } => RecordedKind::Text(color),
+                PrimitiveKind::Other(_) => RecordedKind::Other,
+            };
+
+            self.primitives.push(RecordedPrimitive {
+                widget_id: primitive.id,
+                kind,
+                rect: primitive.rect,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use backend_library::ProgramLibraryManager;
+
+    use input::InputManager;
+    use settings::Settings;
+    use utils::TimeManager;
+    use window::Window;
+    use window::headless::HeadlessEvent;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn ui_is_rendered_without_display() {
+        let library_directory = env::temp_dir().join(format!("program-launcher-headless-{}", process::id()));
+        let _ = fs::remove_dir_all(&library_directory);
+        let mut library = ProgramLibraryManager::new(&library_directory);
+
+        let settings = Settings::default();
+        let mut window = HeadlessWindow::new("Program launcher", 800, 600, false, None);
+        let mut input = InputManager::new(&settings);
+        let mut ui = UiManager::new(settings);
+        let mut renderer = HeadlessRenderer::new();
+        let time_manager = TimeManager::new();
+
+        window.push_key_hit(HeadlessEvent::Down);
+        window.update_input(&mut input, ui.ui_mut(), time_manager.current_time());
+        ui.input_update(&mut input, library.programs());
+        ui.set_widgets(&mut library, &mut window);
+        renderer.render(&mut window, &mut ui);
+
+        assert_eq!(renderer.frame_count(), 1);
+        assert!(!renderer.primitives().is_empty());
+        assert!(!renderer.snapshot().is_empty());
+
+        fs::remove_dir_all(&library_directory).unwrap();
+    }
+}
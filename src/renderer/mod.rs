@@ -1,3 +1,5 @@
+pub mod headless;
+
 
 use conrod::image::Map;
 
@@ -7,7 +9,7 @@ use conrod::backend::glium::glium::Surface;
 
 use conrod::backend::glium::Renderer as UiRenderer;
 
-use window::Window;
+use window::{Window, OpenGLWindow};
 
 use ui::UiManager;
 
@@ -29,13 +31,13 @@ impl OpenGLRenderer {
 }
 
 
-pub trait Renderer {
-    fn render<T: Window>(&mut self, window: &mut T, ui: &mut UiManager);
+pub trait Renderer<T: Window> {
+    fn render(&mut self, window: &mut T, ui: &mut UiManager);
 }
 
 
-impl Renderer for OpenGLRenderer {
-    fn render<T: Window>(&mut self, window: &mut T, ui: &mut UiManager) {
+impl<T: OpenGLWindow> Renderer<T> for OpenGLRenderer {
+    fn render(&mut self, window: &mut T, ui: &mut UiManager) {
         let mut frame = window.draw();
         frame.clear_color(0.0, 0.0, 0.0, 0.0);
 
@@ -0,0 +1,218 @@
+//! Launcher settings which are saved to a settings file.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+use input::bindings::DEFAULT_GAME_CONTROLLER;
+use input::utils::KeyRepeatSettings;
+
+use DEFAULT_WINDOW_HEIGHT;
+use DEFAULT_WINDOW_WIDTH;
+
+/// Settings file name in the config directory, see `paths::settings_file_path`.
+pub const SETTINGS_FILE_NAME: &str = "settings.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+/// Launcher settings. Missing fields are set to default values when loading.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub full_screen: bool,
+    /// Index of the full screen monitor. `None` is the primary monitor.
+    pub full_screen_monitor: Option<usize>,
+    /// Window size when the window is not in full screen mode.
+    pub window_width: u32,
+    pub window_height: u32,
+    /// Max count of console lines.
+    pub console_scrollback_lines: usize,
+    pub theme: Theme,
+    /// Key names for input actions. Key bindings don't have modifier keys, so
+    /// `Ctrl+Tab` and `Ctrl+Shift+Tab`, which also change the tab, are not here.
+    pub key_bindings: BTreeMap<String, Vec<String>>,
+    /// Key repeat timing for input actions. Default timing is used for missing actions.
+    pub key_repeat: BTreeMap<String, KeyRepeatSettings>,
+    /// Stick values between `-game_controller_deadzone` and `game_controller_deadzone` are ignored.
+    pub game_controller_deadzone: f32,
+    /// Game controller button and stick direction names for input actions for each
+    /// controller name. Bindings named `default` are used for other controllers.
+    pub game_controller_bindings: BTreeMap<String, BTreeMap<String, Vec<String>>>,
+    /// Library directory which is used if it is not set from the command line or
+    /// environment variable. Empty string is the default directory.
+    pub library_directory: String,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        let key_bindings = [
+            ("quit", "F10"),
+            ("select", "Return"),
+            ("cancel", "Delete"),
+            ("up", "Up"),
+            ("down", "Down"),
+            ("left", "Left"),
+            ("right", "Right"),
+            ("back", "Escape"),
+            ("page_up", "PageUp"),
+            ("page_down", "PageDown"),
+            ("home", "Home"),
+            ("end", "End"),
+            ("previous_tab", "F8"),
+            ("next_tab", "F9"),
+        ].iter().map(|&(action, key)| (action.to_string(), vec![key.to_string()])).collect();
+
+        let default_game_controller_bindings = [
+            ("select", &["South"][..]),
+            ("cancel", &["West"][..]),
+            ("up", &["DPadUp", "LeftStickUp"][..]),
+            ("down", &["DPadDown", "LeftStickDown"][..]),
+            ("left", &["DPadLeft", "LeftStickLeft"][..]),
+            ("right", &["DPadRight", "LeftStickRight"][..]),
+            ("quit", &["Mode"][..]),
+            ("back", &["East"][..]),
+            ("page_up", &["LeftTrigger2"][..]),
+            ("page_down", &["RightTrigger2"][..]),
+            ("home", &["Select"][..]),
+            ("end", &["Start"][..]),
+            ("previous_tab", &["LeftTrigger"][..]),
+            ("next_tab", &["RightTrigger"][..]),
+        ].iter().map(|&(action, inputs)| {
+            (action.to_string(), inputs.iter().map(|input| input.to_string()).collect())
+        }).collect();
+
+        let list_key_repeat = KeyRepeatSettings {
+            initial_delay_milliseconds: 300,
+            repeat_interval_milliseconds: 100,
+            acceleration: 1.1,
+            min_repeat_interval_milliseconds: 30,
+        };
+
+        let page_key_repeat = KeyRepeatSettings {
+            initial_delay_milliseconds: 400,
+            repeat_interval_milliseconds: 200,
+            acceleration: 1.0,
+            min_repeat_interval_milliseconds: 200,
+        };
+
+        let key_repeat = [
+            ("up", list_key_repeat),
+            ("down", list_key_repeat),
+            ("left", KeyRepeatSettings::default()),
+            ("right", KeyRepeatSettings::default()),
+            ("page_up", page_key_repeat),
+            ("page_down", page_key_repeat),
+        ].iter().map(|&(action, key_repeat)| (action.to_string(), key_repeat)).collect();
+
+        let mut game_controller_bindings = BTreeMap::new();
+        game_controller_bindings.insert(DEFAULT_GAME_CONTROLLER.to_string(), default_game_controller_bindings);
+
+        Settings {
+            full_screen: false,
+            full_screen_monitor: None,
+            window_width: DEFAULT_WINDOW_WIDTH,
+            window_height: DEFAULT_WINDOW_HEIGHT,
+            console_scrollback_lines: 1000,
+            theme: Theme::Light,
+            key_bindings,
+            key_repeat,
+            game_controller_deadzone: 0.3,
+            game_controller_bindings,
+            library_directory: String::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    IoError(io::Error),
+    ParseError(serde_json::Error),
+}
+
+impl Settings {
+    /// Loads settings from a file. Returns default settings if the file does not exist.
+    pub fn load(file_path: &Path) -> Result<Settings, SettingsError> {
+        if !file_path.exists() {
+            return Ok(Settings::default());
+        }
+
+        let mut text = String::new();
+
+        match File::open(file_path).and_then(|mut file| file.read_to_string(&mut text)) {
+            Ok(_) => (),
+            Err(io_error) => return Err(SettingsError::IoError(io_error)),
+        }
+
+        match serde_json::from_str(&text) {
+            Ok(settings) => Ok(settings),
+            Err(parse_error) => Err(SettingsError::ParseError(parse_error)),
+        }
+    }
+
+    /// Saves settings to a temporary file and renames it to `file_path`.
+    /// Temporary file is synced to the disk before renaming.
+    pub fn save(&self, file_path: &Path) -> Result<(), SettingsError> {
+        let text = match serde_json::to_string_pretty(self) {
+            Ok(text) => text,
+            Err(parse_error) => return Err(SettingsError::ParseError(parse_error)),
+        };
+
+        if let Some(directory) = file_path.parent() {
+            if let Err(io_error) = fs::create_dir_all(directory) {
+                return Err(SettingsError::IoError(io_error));
+            }
+        }
+
+        let mut temp_file_path = file_path.as_os_str().to_os_string();
+        temp_file_path.push(".tmp");
+        let temp_file_path = PathBuf::from(temp_file_path);
+
+        let result = File::create(&temp_file_path)
+            .and_then(|mut file| file.write_all(text.as_bytes()).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temp_file_path, file_path));
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(io_error) => Err(SettingsError::IoError(io_error)),
+        }
+    }
+}
+
+/// Copies the settings file to `<file>.bak`, so that a settings file which can't be
+/// loaded is not lost when the settings are saved. If the backup file already
+/// exists, a number is added, for example `settings.json.1.bak`.
+pub fn backup_settings_file(file_path: &Path) -> Result<PathBuf, io::Error> {
+    let content = fs::read(file_path)?;
+
+    for i in 0.. {
+        let mut backup_file_path = file_path.as_os_str().to_os_string();
+
+        if i == 0 {
+            backup_file_path.push(".bak");
+        } else {
+            backup_file_path.push(format!(".{}.bak", i));
+        }
+
+        let backup_file_path = PathBuf::from(backup_file_path);
+
+        match OpenOptions::new().write(true).create_new(true).open(&backup_file_path) {
+            Ok(mut file) => {
+                file.write_all(&content)?;
+                file.sync_all()?;
+                return Ok(backup_file_path);
+            }
+            Err(ref error) if error.kind() == io::ErrorKind::AlreadyExists => (),
+            Err(error) => return Err(error),
+        }
+    }
+
+    unreachable!()
+}
@@ -0,0 +1,855 @@
+//! Edit tab for editing programs of the library file.
+//!
+//! Changes are made to a draft of the selected program. The draft is
+//! saved to the library file with `EditAction::Save`.
+
+use conrod::UiCell;
+use conrod::widget;
+use conrod::position::Scalar;
+
+use backend_library::data::{LibraryFileProgram, CommandQueue, CommandData};
+use backend_library::ProgramLibraryManager;
+
+use super::{WidgetIds, ClickMode};
+use super::theme::{MessageKind, Palette};
+
+const ROW_HEIGHT: Scalar = 34.0;
+const LABEL_WIDTH: Scalar = 150.0;
+const BUTTON_WIDTH: Scalar = 180.0;
+
+pub const KEYBOARD_HELP: &str = "Tab: next field, Ctrl+S: save, Ctrl+N: new program, Ctrl+Q: new command queue, \
+    Ctrl+K: new command, Ctrl+D: remove focused item, Ctrl+Delete: remove program, Ctrl+Tab: next tab";
+
+/// Text field of the program draft.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Name,
+    WorkingDirectory,
+    DownloadExecutable,
+    DownloadArgs,
+    QueueName(usize),
+    Executable(usize, usize),
+    Args(usize, usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditAction {
+    Save,
+    Revert,
+    AddProgram,
+    RemoveProgram,
+    AddDownloadCommand,
+    RemoveDownloadCommand,
+    AddQueue,
+    RemoveQueue(usize),
+    AddCommand(usize),
+    RemoveCommand(usize, usize),
+}
+
+struct CommandDraft {
+    executable: String,
+    /// Arguments as one line, see `split_args`.
+    args: String,
+    /// Other fields of the command are kept from here.
+    original: CommandData,
+}
+
+impl CommandDraft {
+    fn new(command: &CommandData) -> CommandDraft {
+        CommandDraft {
+            executable: command.executable.clone(),
+            args: join_args(&command.args),
+            original: command.clone(),
+        }
+    }
+
+    fn to_command(&self) -> CommandData {
+        let mut command = self.original.clone();
+        command.executable = self.executable.clone();
+        command.args = split_args(&self.args);
+        command
+    }
+}
+
+struct QueueDraft {
+    name: String,
+    commands: Vec<CommandDraft>,
+    original: CommandQueue,
+}
+
+/// Program with text fields as they are in the text boxes.
+struct ProgramDraft {
+    name: String,
+    working_directory: String,
+    download_command: Option<CommandDraft>,
+    command_queues: Vec<QueueDraft>,
+    original: LibraryFileProgram,
+}
+
+impl ProgramDraft {
+    fn new(program: &LibraryFileProgram) -> ProgramDraft {
+        ProgramDraft {
+            name: program.name.clone(),
+            working_directory: program.working_directory.clone(),
+            download_command: program.download_command.as_ref().map(CommandDraft::new),
+            command_queues: program.command_queues.iter().map(|queue| {
+                QueueDraft {
+                    name: queue.name.clone(),
+                    commands: queue.commands.iter().map(CommandDraft::new).collect(),
+                    original: queue.clone(),
+                }
+            }).collect(),
+            original: program.clone(),
+        }
+    }
+
+    fn to_program(&self) -> LibraryFileProgram {
+        let mut program = self.original.clone();
+        program.name = self.name.clone();
+        program.working_directory = self.working_directory.clone();
+        program.download_command = self.download_command.as_ref().map(CommandDraft::to_command);
+        program.command_queues = self.command_queues.iter().map(|queue| {
+            let mut command_queue = queue.original.clone();
+            command_queue.name = queue.name.clone();
+            command_queue.commands = queue.commands.iter().map(CommandDraft::to_command).collect();
+            command_queue
+        }).collect();
+        program
+    }
+
+    fn field_mut(&mut self, field: Field) -> Option<&mut String> {
+        match field {
+            Field::Name => Some(&mut self.name),
+            Field::WorkingDirectory => Some(&mut self.working_directory),
+            Field::DownloadExecutable => self.download_command.as_mut().map(|command| &mut command.executable),
+            Field::DownloadArgs => self.download_command.as_mut().map(|command| &mut command.args),
+            Field::QueueName(q) => self.command_queues.get_mut(q).map(|queue| &mut queue.name),
+            Field::Executable(q, c) => self.command_mut(q, c).map(|command| &mut command.executable),
+            Field::Args(q, c) => self.command_mut(q, c).map(|command| &mut command.args),
+        }
+    }
+
+    fn command_mut(&mut self, q: usize, c: usize) -> Option<&mut CommandDraft> {
+        self.command_queues.get_mut(q).and_then(|queue| queue.commands.get_mut(c))
+    }
+
+    /// Rows of the edit form.
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = vec![
+            Row::Field("Name".to_string(), Field::Name),
+            Row::Field("Working directory".to_string(), Field::WorkingDirectory),
+            Row::Heading("Download command".to_string()),
+        ];
+
+        if self.download_command.is_some() {
+            rows.push(Row::Field("Executable".to_string(), Field::DownloadExecutable));
+            rows.push(Row::Field("Arguments".to_string(), Field::DownloadArgs));
+            rows.push(Row::Buttons(vec![("Remove download command", EditAction::RemoveDownloadCommand)]));
+        } else {
+            rows.push(Row::Buttons(vec![("Add download command", EditAction::AddDownloadCommand)]));
+        }
+
+        for (q, queue) in self.command_queues.iter().enumerate() {
+            rows.push(Row::Heading(format!("Command queue {}", q + 1)));
+            rows.push(Row::Field("Name".to_string(), Field::QueueName(q)));
+
+            for c in 0..queue.commands.len() {
+                rows.push(Row::Field(format!("Command {} executable", c + 1), Field::Executable(q, c)));
+                rows.push(Row::Field("Arguments".to_string(), Field::Args(q, c)));
+                rows.push(Row::Buttons(vec![("Remove command", EditAction::RemoveCommand(q, c))]));
+            }
+
+            rows.push(Row::Buttons(vec![("Add command", EditAction::AddCommand(q)), ("Remove command queue", EditAction::RemoveQueue(q))]));
+        }
+
+        rows.push(Row::Buttons(vec![("Add command queue", EditAction::AddQueue)]));
+
+        rows
+    }
+}
+
+enum Row {
+    Heading(String),
+    Field(String, Field),
+    Buttons(Vec<(&'static str, EditAction)>),
+}
+
+/// Where keyboard focus should be moved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FocusRequest {
+    Field(Field),
+    Next,
+    Previous,
+    /// Remove focus from text fields.
+    None,
+}
+
+pub struct EditState {
+    draft: Option<ProgramDraft>,
+    /// Index of the program which the draft is from.
+    draft_program_i: usize,
+    /// Draft has unsaved changes.
+    modified: bool,
+    /// Program removal needs to be confirmed.
+    remove_program_armed: bool,
+    message: Option<(String, MessageKind)>,
+    /// Text fields and their widget ids in focus order.
+    field_ids: Vec<(Field, widget::Id)>,
+    focus_request: Option<FocusRequest>,
+}
+
+impl EditState {
+    pub fn new() -> EditState {
+        EditState {
+            draft: None,
+            draft_program_i: 0,
+            modified: false,
+            remove_program_armed: false,
+            message: None,
+            field_ids: vec![],
+            focus_request: None,
+        }
+    }
+
+    pub fn modified(&self) -> bool {
+        self.modified
+    }
+
+    pub fn set_message(&mut self, text: String, kind: MessageKind) {
+        self.message = Some((text, kind));
+    }
+
+    /// Library file was changed by someone else. Unsaved draft is kept and
+    /// the program is searched from `programs` by its name, because the
+    /// program may have been moved.
+    pub fn library_reloaded(&mut self, programs: &[LibraryFileProgram], selection_i: &mut usize) {
+        if !self.modified {
+            self.draft = None;
+            return;
+        }
+
+        let name = match self.draft {
+            Some(ref draft) => draft.original.name.clone(),
+            None => return,
+        };
+
+        match programs.iter().position(|program| program.name == name) {
+            Some(i) => {
+                self.draft_program_i = i;
+                *selection_i = i;
+            }
+            None => {
+                let message = format!("Program `{}` was removed from the library file, revert changes to continue", name);
+                self.set_message(message, MessageKind::Error);
+            }
+        }
+    }
+
+    /// Returns error message if the program which the draft is from has
+    /// been changed in the library file.
+    fn draft_conflict(&self, library: &ProgramLibraryManager) -> Option<String> {
+        let draft = self.draft.as_ref()?;
+        let programs = library.programs().library_file_programs().unwrap_or(&[]);
+
+        match programs.get(self.draft_program_i) {
+            Some(program) if *program == draft.original => None,
+            _ => Some(format!("Program `{}` was changed outside of the launcher, revert changes to load it again", draft.original.name)),
+        }
+    }
+
+    /// Returns true if widget is one of the text fields.
+    pub fn is_text_field(&self, id: widget::Id) -> bool {
+        self.field_ids.iter().any(|&(_, field_id)| field_id == id)
+    }
+
+    pub fn request_focus(&mut self, request: FocusRequest) {
+        self.focus_request = Some(request);
+    }
+
+    /// Returns widget which should capture the keyboard. Argument `focused` is
+    /// the widget currently capturing the keyboard. Outer Option is None if
+    /// focus should not be changed.
+    pub fn take_focus_request(&mut self, focused: Option<widget::Id>) -> Option<Option<widget::Id>> {
+        let current_i = focused.and_then(|id| self.field_ids.iter().position(|&(_, field_id)| field_id == id));
+        let count = self.field_ids.len();
+
+        let new_i = match self.focus_request.take()? {
+            FocusRequest::None => return Some(None),
+            _ if count == 0 => return None,
+            FocusRequest::Field(field) => self.field_ids.iter().position(|&(f, _)| f == field)?,
+            FocusRequest::Next => current_i.map(|i| (i + 1) % count).unwrap_or(0),
+            FocusRequest::Previous => current_i.map(|i| (i + count - 1) % count).unwrap_or(count - 1),
+        };
+
+        Some(Some(self.field_ids[new_i].1))
+    }
+
+    fn focused_field(&self, focused: Option<widget::Id>) -> Option<Field> {
+        let focused = focused?;
+        self.field_ids.iter().find(|&&(_, id)| id == focused).map(|&(field, _)| field)
+    }
+
+    /// Action for keyboard shortcut `Ctrl+D`.
+    fn remove_focused_action(&self, focused: Option<widget::Id>) -> Option<EditAction> {
+        match self.focused_field(focused)? {
+            Field::DownloadExecutable | Field::DownloadArgs => Some(EditAction::RemoveDownloadCommand),
+            Field::QueueName(q) => Some(EditAction::RemoveQueue(q)),
+            Field::Executable(q, c) | Field::Args(q, c) => Some(EditAction::RemoveCommand(q, c)),
+            Field::Name | Field::WorkingDirectory => None,
+        }
+    }
+
+    /// Action for keyboard shortcut `Ctrl+K`.
+    fn add_command_action(&self, focused: Option<widget::Id>) -> Option<EditAction> {
+        let queue_count = self.draft.as_ref().map(|draft| draft.command_queues.len()).unwrap_or(0);
+
+        match self.focused_field(focused) {
+            Some(Field::QueueName(q)) | Some(Field::Executable(q, _)) | Some(Field::Args(q, _)) => Some(EditAction::AddCommand(q)),
+            _ if queue_count > 0 => Some(EditAction::AddCommand(queue_count - 1)),
+            _ => None,
+        }
+    }
+
+    /// Applies action. Changes to the library file are made with `library`.
+    pub fn apply(&mut self, action: EditAction, library: &mut ProgramLibraryManager, selection_i: &mut usize) {
+        if action != EditAction::RemoveProgram {
+            self.remove_program_armed = false;
+        }
+
+        match action {
+            EditAction::Save => {
+                let program = match self.draft {
+                    Some(ref draft) => draft.to_program(),
+                    None => return,
+                };
+
+                if let Some(message) = self.draft_conflict(library) {
+                    self.set_message(message, MessageKind::Error);
+                    return;
+                }
+
+                match library.edit_program(self.draft_program_i, |p| *p = program) {
+                    Ok(()) => {
+                        self.draft = None;
+                        self.modified = false;
+                        self.set_message("Saved".to_string(), MessageKind::Success);
+                    }
+                    Err(error) => self.set_message(format!("Saving failed: {}", error), MessageKind::Error),
+                }
+            }
+            EditAction::Revert => {
+                self.draft = None;
+                self.modified = false;
+                self.message = None;
+            }
+            EditAction::AddProgram => {
+                if self.modified {
+                    self.set_message("Save or revert changes first".to_string(), MessageKind::Error);
+                    return;
+                }
+
+                let name = {
+                    let programs = library.programs().library_file_programs().unwrap_or(&[]);
+                    let mut name = "New program".to_string();
+                    let mut n = 1;
+
+                    while programs.iter().any(|program| program.name == name) {
+                        n += 1;
+                        name = format!("New program {}", n);
+                    }
+
+                    name
+                };
+
+                let program = LibraryFileProgram {
+                    working_directory: name.to_lowercase().replace(' ', "_"),
+                    name,
+                    ..LibraryFileProgram::default()
+                };
+
+                match library.add_program(program) {
+                    Ok(()) => {
+                        *selection_i = library.programs().library_file_programs().map(|programs| programs.len() - 1).unwrap_or(0);
+                        self.draft = None;
+                        self.message = None;
+                        self.request_focus(FocusRequest::Field(Field::Name));
+                    }
+                    Err(error) => self.set_message(format!("Adding program failed: {}", error), MessageKind::Error),
+                }
+            }
+            EditAction::RemoveProgram => {
+                let name = match self.draft {
+                    Some(ref draft) => draft.original.name.clone(),
+                    None => return,
+                };
+
+                if let Some(message) = self.draft_conflict(library) {
+                    self.remove_program_armed = false;
+                    self.set_message(message, MessageKind::Error);
+                    return;
+                }
+
+                if !self.remove_program_armed {
+                    self.remove_program_armed = true;
+                    self.set_message(format!("Remove program `{}` again to confirm", name), MessageKind::Error);
+                    return;
+                }
+
+                self.remove_program_armed = false;
+
+                match library.remove_program(self.draft_program_i) {
+                    Ok(_) => {
+                        *selection_i = selection_i.saturating_sub(1);
+                        self.draft = None;
+                        self.modified = false;
+                        self.set_message(format!("Program `{}` removed", name), MessageKind::Success);
+                    }
+                    Err(error) => self.set_message(format!("Removing program failed: {}", error), MessageKind::Error),
+                }
+            }
+            action => self.apply_to_draft(action),
+        }
+    }
+
+    /// Applies action which only changes the draft.
+    fn apply_to_draft(&mut self, action: EditAction) {
+        let draft = match self.draft {
+            Some(ref mut draft) => draft,
+            None => return,
+        };
+
+        let new_command = || CommandDraft::new(&CommandData::default());
+
+        let focus = match action {
+            EditAction::AddDownloadCommand => {
+                draft.download_command = Some(new_command());
+                Some(Field::DownloadExecutable)
+            }
+            EditAction::RemoveDownloadCommand => {
+                draft.download_command = None;
+                None
+            }
+            EditAction::AddQueue => {
+                draft.command_queues.push(QueueDraft {
+                    name: String::new(),
+                    commands: vec![new_command()],
+                    original: CommandQueue::default(),
+                });
+                Some(Field::QueueName(draft.command_queues.len() - 1))
+            }
+            EditAction::RemoveQueue(q) if q < draft.command_queues.len() => {
+                draft.command_queues.remove(q);
+                None
+            }
+            EditAction::AddCommand(q) if q < draft.command_queues.len() => {
+                let commands = &mut draft.command_queues[q].commands;
+                commands.push(new_command());
+                Some(Field::Executable(q, commands.len() - 1))
+            }
+            EditAction::RemoveCommand(q, c) if q < draft.command_queues.len() && c < draft.command_queues[q].commands.len() => {
+                draft.command_queues[q].commands.remove(c);
+                None
+            }
+            _ => return,
+        };
+
+        self.modified = true;
+
+        match focus {
+            Some(field) => self.request_focus(FocusRequest::Field(field)),
+            None => self.request_focus(FocusRequest::None),
+        }
+    }
+}
+
+/// Draws the Edit tab to `ids.canvas_tab` and returns actions which need
+/// the `ProgramLibraryManager`.
+pub fn set_widgets(ui_cell: &mut UiCell, ids: &mut WidgetIds, state: &mut EditState, selection_i: &mut usize, library: &ProgramLibraryManager, palette: &Palette) -> Vec<EditAction> {
+    use conrod::widget::{Canvas, Widget, Button, Text, TextBox, ListSelect};
+    use conrod::widget::list_select::{Event, Single};
+    use conrod::widget::text_box;
+    use conrod::{Colorable, Labelable, Positionable, Sizeable};
+    use conrod::event;
+    use conrod::input::Key;
+    use conrod::input::keyboard::{CTRL, SHIFT};
+
+    let mut actions = vec![];
+
+    // Keyboard shortcuts
+
+    let focused = ui_cell.global_input().current.widget_capturing_keyboard;
+
+    for event in ui_cell.global_input().events().ui() {
+        if let event::Ui::Press(_, event::Press { button: event::Button::Keyboard(key), modifiers }) = *event {
+            let ctrl = modifiers.contains(CTRL);
+
+            match key {
+                Key::Tab if !ctrl && modifiers.contains(SHIFT) => state.request_focus(FocusRequest::Previous),
+                Key::Tab if !ctrl => state.request_focus(FocusRequest::Next),
+                Key::Escape => state.request_focus(FocusRequest::None),
+                Key::S if ctrl => actions.push(EditAction::Save),
+                Key::N if ctrl => actions.push(EditAction::AddProgram),
+                Key::Q if ctrl => actions.push(EditAction::AddQueue),
+                Key::K if ctrl => actions.extend(state.add_command_action(focused)),
+                Key::D if ctrl => actions.extend(state.remove_focused_action(focused)),
+                Key::Delete if ctrl => actions.push(EditAction::RemoveProgram),
+                _ => (),
+            }
+        }
+    }
+
+    // Layout
+
+    Canvas::new()
+        .top_left_of(ids.canvas_tab)
+        .wh_of(ids.canvas_tab)
+        .flow_right(&[
+            (ids.canvas_edit_left, Canvas::new().color(palette.list_panel).length(250.0)),
+            (ids.canvas_edit_right, Canvas::new().flow_down(&[
+                (ids.canvas_edit_toolbar, Canvas::new().color(palette.panel).pad(10.0).length(110.0)),
+                (ids.canvas_edit_form, Canvas::new().color(palette.panel).pad(10.0).scroll_kids_vertically()),
+            ])),
+        ])
+        .set(ids.canvas_edit_layout, ui_cell);
+
+    let library_file_programs = match library.programs().library_file_programs() {
+        Some(programs) => programs,
+        None => {
+            state.field_ids.clear();
+
+            Text::new("Library file has errors. Fix the errors before editing the library.")
+                .top_left_of(ids.canvas_edit_toolbar)
+                .w_of(ids.canvas_edit_toolbar)
+                .color(palette.error)
+                .set(ids.edit_message_text, ui_cell);
+
+            return actions;
+        }
+    };
+
+    // Program list
+
+    let (mut events, scrollbar) = ListSelect::new(library_file_programs.len(), ClickMode(Single{}))
+        .flow_down()
+        .scrollbar_next_to()
+        .item_size(30.0)
+        .w_of(ids.canvas_edit_left)
+        .h(ui_cell.h_of(ids.canvas_edit_left).unwrap_or(0.0) - 40.0)
+        .top_left_of(ids.canvas_edit_left)
+        .set(ids.edit_program_list, ui_cell);
+
+    while let Some(event) = events.next(ui_cell, |i| i == *selection_i) {
+        match event {
+            Event::Item(item) => {
+                let color = if item.i == *selection_i {
+                    palette.button_selected
+                } else {
+                    palette.button
+                };
+                let button = Button::new()
+                    .color(color)
+                    .label_color(palette.button_label)
+                    .label(&library_file_programs[item.i].name);
+                item.set(button, ui_cell);
+            },
+            Event::Selection(selection) => {
+                if state.modified && selection != *selection_i {
+                    state.set_message("Save or revert changes first".to_string(), MessageKind::Error);
+                } else {
+                    *selection_i = selection;
+                }
+            },
+            _ => (),
+        }
+    }
+
+    if let Some(s) = scrollbar {
+        s.set(ui_cell);
+    }
+
+    for _click in Button::new()
+        .label("Add program")
+        .label_color(palette.button_label)
+        .color(palette.button)
+        .w_h(250.0, 40.0)
+        .bottom_left_of(ids.canvas_edit_left)
+        .set(ids.edit_add_program_button, ui_cell)
+    {
+        actions.push(EditAction::AddProgram);
+    }
+
+    // Draft
+
+    if state.draft.is_none() || state.draft_program_i != *selection_i {
+        state.draft = library_file_programs.get(*selection_i).map(ProgramDraft::new);
+        state.draft_program_i = *selection_i;
+        state.modified = false;
+    }
+
+    // Toolbar
+
+    let toolbar_buttons = [
+        (ids.edit_save_button, "Save", EditAction::Save),
+        (ids.edit_revert_button, "Revert", EditAction::Revert),
+        (ids.edit_remove_program_button, "Remove program", EditAction::RemoveProgram),
+    ];
+
+    for (i, &(id, label, action)) in toolbar_buttons.iter().enumerate() {
+        let color = if action == EditAction::Save && state.modified {
+            palette.button_selected
+        } else {
+            palette.button_inactive
+        };
+
+        for _click in Button::new()
+            .label(label)
+            .label_color(palette.button_label)
+            .color(color)
+            .w_h(140.0, 30.0)
+            .top_left_with_margins_on(ids.canvas_edit_toolbar, 0.0, i as Scalar * 150.0)
+            .set(id, ui_cell)
+        {
+            actions.push(action);
+        }
+    }
+
+    let (message, message_color) = match state.message {
+        Some((ref text, kind)) => (text.as_str(), palette.message(kind)),
+        None => (KEYBOARD_HELP, palette.text),
+    };
+
+    Text::new(message)
+        .top_left_with_margins_on(ids.canvas_edit_toolbar, 40.0, 0.0)
+        .w_of(ids.canvas_edit_toolbar)
+        .font_size(12)
+        .color(message_color)
+        .set(ids.edit_message_text, ui_cell);
+
+    // Form
+
+    let draft = match state.draft {
+        Some(ref mut draft) => draft,
+        None => {
+            state.field_ids.clear();
+            return actions;
+        }
+    };
+
+    let rows = draft.rows();
+
+    let field_count = rows.iter().filter(|row| if let Row::Field(..) = **row { true } else { false }).count();
+    let button_count: usize = rows.iter().map(|row| if let Row::Buttons(ref buttons) = *row { buttons.len() } else { 0 }).sum();
+
+    ids.edit_labels.resize(rows.len(), &mut ui_cell.widget_id_generator());
+    ids.edit_text_boxes.resize(field_count, &mut ui_cell.widget_id_generator());
+    ids.edit_buttons.resize(button_count, &mut ui_cell.widget_id_generator());
+
+    let form_width = ui_cell.kid_area_of(ids.canvas_edit_form).map(|rect| rect.w()).unwrap_or(0.0);
+    let text_box_width = (form_width - LABEL_WIDTH - 10.0).max(100.0);
+
+    let mut field_ids = vec![];
+    let mut button_i = 0;
+
+    for (row_i, row) in rows.iter().enumerate() {
+        let top = row_i as Scalar * ROW_HEIGHT;
+
+        match *row {
+            Row::Heading(ref text) => {
+                Text::new(text)
+                    .top_left_with_margins_on(ids.canvas_edit_form, top + 8.0, 0.0)
+                    .font_size(16)
+                    .set(ids.edit_labels[row_i], ui_cell);
+            }
+            Row::Field(ref label, field) => {
+                Text::new(label)
+                    .top_left_with_margins_on(ids.canvas_edit_form, top + 8.0, 0.0)
+                    .font_size(12)
+                    .set(ids.edit_labels[row_i], ui_cell);
+
+                let id = ids.edit_text_boxes[field_ids.len()];
+                field_ids.push((field, id));
+
+                let text = match draft.field_mut(field) {
+                    Some(text) => text,
+                    None => continue,
+                };
+
+                for event in TextBox::new(text.as_str())
+                    .font_size(14)
+                    .w_h(text_box_width, ROW_HEIGHT - 6.0)
+                    .top_left_with_margins_on(ids.canvas_edit_form, top, LABEL_WIDTH)
+                    .set(id, ui_cell)
+                {
+                    match event {
+                        text_box::Event::Update(new_text) => {
+                            *text = new_text.replace('\t', "");
+                            state.modified = true;
+                        }
+                        text_box::Event::Enter => state.focus_request = Some(FocusRequest::Next),
+                    }
+                }
+            }
+            Row::Buttons(ref buttons) => {
+                for (i, &(label, action)) in buttons.iter().enumerate() {
+                    for _click in Button::new()
+                        .label(label)
+                        .label_color(palette.button_label)
+                        .color(palette.button_inactive)
+                        .w_h(BUTTON_WIDTH, ROW_HEIGHT - 6.0)
+                        .top_left_with_margins_on(ids.canvas_edit_form, top, LABEL_WIDTH + i as Scalar * (BUTTON_WIDTH + 10.0))
+                        .set(ids.edit_buttons[button_i], ui_cell)
+                    {
+                        actions.push(action);
+                    }
+
+                    button_i += 1;
+                }
+            }
+        }
+    }
+
+    state.field_ids = field_ids;
+
+    actions
+}
+
+/// Joins arguments to one line. Arguments which are empty or contain
+/// whitespace, `"` or `\` are quoted.
+pub fn join_args(args: &[String]) -> String {
+    let args: Vec<String> = args.iter().map(|arg| {
+        if !arg.is_empty() && !arg.chars().any(|c| c.is_whitespace() || c == '"' || c == '\\') {
+            return arg.clone();
+        }
+
+        let mut quoted = String::from("\"");
+
+        for c in arg.chars() {
+            if c == '"' || c == '\\' {
+                quoted.push('\\');
+            }
+
+            quoted.push(c);
+        }
+
+        quoted.push('"');
+        quoted
+    }).collect();
+
+    args.join(" ")
+}
+
+/// Splits line to arguments at whitespace. Use `"` to include whitespace
+/// in an argument and `\` to escape the next character.
+pub fn split_args(line: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut current: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let arg = current.get_or_insert_with(String::new);
+
+                if let Some(next) = chars.next() {
+                    arg.push(next);
+                }
+            }
+            '"' => {
+                current.get_or_insert_with(String::new);
+                quoted = !quoted;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if let Some(arg) = current.take() {
+                    args.push(arg);
+                }
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if let Some(arg) = current {
+        args.push(arg);
+    }
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn args_are_split_at_whitespace() {
+        assert_eq!(split_args("  run  --release\t-v "), strings(&["run", "--release", "-v"]));
+        assert!(split_args("").is_empty());
+    }
+
+    #[test]
+    fn quotes_and_backslashes_are_removed_when_splitting() {
+        assert_eq!(split_args(r#""a b" c"d e"f"#), strings(&["a b", "cd ef"]));
+        assert_eq!(split_args(r#"a\ b \"c\" d\\e"#), strings(&["a b", "\"c\"", "d\\e"]));
+        assert_eq!(split_args(r#""" a """#), strings(&["", "a", ""]));
+    }
+
+    #[test]
+    fn joined_args_are_split_to_the_same_args() {
+        let args = strings(&["plain", "with space", "", "quote\"d", "back\\slash", "ä"]);
+        let line = join_args(&args);
+
+        assert_eq!(line, r#"plain "with space" "" "quote\"d" "back\\slash" ä"#);
+        assert_eq!(split_args(&line), args);
+
+        let args = strings(&["tab\there", "new\nline"]);
+        assert_eq!(split_args(&join_args(&args)), args);
+    }
+
+    fn program(name: &str) -> LibraryFileProgram {
+        LibraryFileProgram {
+            name: name.to_string(),
+            working_directory: name.to_string(),
+            ..LibraryFileProgram::default()
+        }
+    }
+
+    /// Edit state with modified draft of `program`.
+    fn modified_state(program: &LibraryFileProgram, program_i: usize) -> EditState {
+        let mut state = EditState::new();
+        state.draft = Some(ProgramDraft::new(program));
+        state.draft_program_i = program_i;
+        state.modified = true;
+        state
+    }
+
+    #[test]
+    fn modified_draft_follows_moved_program() {
+        let mut state = modified_state(&program("b"), 1);
+        let mut selection_i = 1;
+
+        state.library_reloaded(&[program("c"), program("a"), program("b")], &mut selection_i);
+
+        assert_eq!(state.draft_program_i, 2);
+        assert_eq!(selection_i, 2);
+        assert!(state.modified());
+    }
+
+    #[test]
+    fn modified_draft_of_removed_program_is_conflict() {
+        let mut state = modified_state(&program("b"), 1);
+        let mut selection_i = 1;
+
+        state.library_reloaded(&[program("a"), program("c")], &mut selection_i);
+
+        assert_eq!(selection_i, 1);
+        assert!(state.modified());
+
+        match state.message {
+            Some((_, MessageKind::Error)) => (),
+            _ => panic!("removed program was not reported"),
+        }
+    }
+}
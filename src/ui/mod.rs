@@ -2,18 +2,38 @@
 
 
 use conrod::{UiBuilder, UiCell, Ui};
+use conrod::position::Scalar;
 
 use DEFAULT_WINDOW_HEIGHT;
 use DEFAULT_WINDOW_WIDTH;
 
-use backend_library::task_manager::TaskManager;
-use backend_library::data::ProgramLibrary;
+use conrod::text::rusttype::FontCollection;
+
+/// Font is included in the executable, so that it works from any directory.
+const FONT: &[u8] = include_bytes!("../../font/OpenSans-Regular.ttf");
 
-use std::collections::VecDeque;
+use conrod::widget::list_select::{Mode, State, PendingEvents, Direction, Single};
+use conrod::event::{Click, KeyPress};
+
+use backend_library::task_manager::{TaskManager, ConsoleLine, ConsoleLineSource};
+use backend_library::data::ProgramLibrary;
+use backend_library::validation::Diagnostic;
+use backend_library::{Event, ProgramLibraryManager};
 
 use window::Window;
 
 use input::Input;
+use input::bindings::{Action, key_name, rebind_key};
+
+use settings::{Settings, Theme};
+
+use std::mem;
+
+pub mod edit;
+pub mod theme;
+
+use self::edit::{EditState, FocusRequest};
+use self::theme::{MessageKind, Palette};
 
 pub struct UiManager {
     widget_ids: WidgetIds,
@@ -21,15 +41,62 @@ pub struct UiManager {
     list_selection_index: usize,
     command_queue_i: usize,
     launch_command_queue: bool,
-    console_text: String,
+    cancel_running_process: bool,
+    console: ConsoleState,
+    queue_status: QueueStatus,
+    current_tab: Tab,
+    edit: EditState,
+    /// Update ui at next frame.
+    redraw: bool,
+    settings: Settings,
+    /// Settings were changed from the Settings tab.
+    settings_changed: bool,
+    palette: Palette,
+    /// Next key press will be bound to this action.
+    rebind_action: Option<Action>,
+    /// Error from the latest key binding change.
+    key_binding_error: String,
+}
+
+const CONSOLE_LINE_HEIGHT: Scalar = 16.0;
+const TAB_BAR_HEIGHT: Scalar = 40.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tab {
+    Library,
+    Edit,
+    Settings,
+}
+
+/// Tabs in the order of the tab bar.
+const TABS: &[(Tab, &str)] = &[(Tab::Library, "Library"), (Tab::Edit, "Edit"), (Tab::Settings, "Settings")];
+
+struct ConsoleState {
+    /// Show only lines from stderr.
+    stderr_only: bool,
+    /// Number of lines scrolled up from the newest line.
+    scroll_offset: usize,
+    /// Number of visible lines.
+    page_lines: usize,
+}
+
+/// Status of the latest command queue.
+struct QueueStatus {
+    text: String,
+    kind: MessageKind,
 }
 
 impl UiManager {
-    pub fn new() -> UiManager {
+    pub fn new(settings: Settings) -> UiManager {
         let mut ui = UiBuilder::new([DEFAULT_WINDOW_WIDTH as f64, DEFAULT_WINDOW_HEIGHT as f64]).build();
         let widget_ids = WidgetIds::new(ui.widget_id_generator());
 
-        ui.fonts.insert_from_file("font/OpenSans-Regular.ttf").unwrap();
+        let font = FontCollection::from_bytes(FONT).into_font().expect("font loading error");
+        ui.fonts.insert(font);
+
+        let palette = Palette::new(settings.theme);
+        ui.theme.label_color = palette.text;
+        ui.theme.shape_color = palette.button;
 
         UiManager {
             widget_ids,
@@ -37,10 +104,36 @@ impl UiManager {
             list_selection_index: 0,
             command_queue_i: 0,
             launch_command_queue: false,
-            console_text: String::new(),
+            cancel_running_process: false,
+            console: ConsoleState {
+                stderr_only: false,
+                scroll_offset: 0,
+                page_lines: 0,
+            },
+            queue_status: QueueStatus {
+                text: String::new(),
+                kind: MessageKind::Normal,
+            },
+            current_tab: Tab::Library,
+            edit: EditState::new(),
+            redraw: false,
+            settings,
+            settings_changed: false,
+            palette,
+            rebind_action: None,
+            key_binding_error: String::new(),
         }
     }
 
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Returns true if settings were changed after the previous call.
+    pub fn take_settings_changed(&mut self) -> bool {
+        mem::replace(&mut self.settings_changed, false)
+    }
+
     pub fn ui_mut(&mut self) -> &mut Ui {
         &mut self.ui
     }
@@ -49,29 +142,128 @@ impl UiManager {
         &self.ui
     }
 
-    pub fn update_console_text(&mut self, console_text: &VecDeque<String>) {
-        // TODO: long console lines may cause problems like running out of memory
-        // TODO: copying all text at every update is inefficient
-        self.console_text.clear();
+    /// Returns true if a text field has keyboard focus or a key binding is being changed.
+    pub fn text_field_focused(&self) -> bool {
+        if self.current_tab == Tab::Settings && self.rebind_action.is_some() {
+            return true;
+        }
 
-        for text in console_text {
-            self.console_text.push_str(text);
-            self.console_text.push('\n');
+        match (self.ui.global_input().current.widget_capturing_keyboard, self.current_tab) {
+            (Some(id), Tab::Edit) => self.edit.is_text_field(id),
+            (Some(id), Tab::Settings) => id == self.widget_ids.text_box_library_directory,
+            _ => false,
         }
     }
 
+    /// Updates command queue status from backend event.
+    pub fn backend_event(&mut self, event: &Event, programs: &ProgramLibrary) {
+        let (text, kind) = match *event {
+            Event::QueueStarted(ref name) => (format!("Running: {}", name), MessageKind::Running),
+            Event::DownloadStarted(_) => ("Downloading".to_string(), MessageKind::Info),
+            Event::SpawnFailed(ref error) => (format!("Failed to start: {}", error), MessageKind::Error),
+            Event::QueueFinished { success: true } => ("Finished".to_string(), MessageKind::Success),
+            Event::QueueFinished { success: false } => ("Failed".to_string(), MessageKind::Error),
+            Event::ConsoleUpdate |
+            Event::CommandStarted(_) |
+            Event::CommandExited(_) |
+            Event::LibraryReloadFailed => return,
+            Event::LibraryReloaded => {
+                let library_file_programs = programs.library_file_programs().unwrap_or(&[]);
+                self.edit.library_reloaded(library_file_programs, &mut self.list_selection_index);
+                return;
+            }
+        };
+
+        self.queue_status = QueueStatus { text, kind };
+    }
+
     /// Return true if ui needs updating
     pub fn input_update<T: Input>(&mut self, input: &mut T, programs: &ProgramLibrary) -> bool {
-        if programs.programs.len() == 0 {
-            return false;
+        let mut update_ui = false;
+
+        if input.cancel() {
+            self.cancel_running_process = true;
+            update_ui = true;
         }
 
-        let mut update_ui = false;
+        if mem::replace(&mut self.redraw, false) {
+            update_ui = true;
+        }
+
+        if input.next_tab() {
+            self.set_tab(cycle_tab(self.current_tab, true));
+            update_ui = true;
+        }
+
+        if input.previous_tab() {
+            self.set_tab(cycle_tab(self.current_tab, false));
+            update_ui = true;
+        }
+
+        if input.back() {
+            if self.current_tab == Tab::Library {
+                // Show the newest console lines.
+                self.console.scroll_offset = 0;
+            } else {
+                self.set_tab(Tab::Library);
+            }
+
+            update_ui = true;
+        }
+
+        if self.current_tab == Tab::Library {
+            if input.page_up() {
+                self.console.scroll_offset += self.console.page_lines.max(1);
+                update_ui = true;
+            }
+
+            if input.page_down() {
+                self.console.scroll_offset = self.console.scroll_offset.saturating_sub(self.console.page_lines.max(1));
+                update_ui = true;
+            }
+        }
+
+        if self.current_tab == Tab::Edit {
+            if input.select() {
+                self.edit.request_focus(FocusRequest::Next);
+                update_ui = true;
+            }
+
+            input.left();
+            input.right();
+
+            if self.edit.modified() && (input.up() || input.down() || input.home() || input.end()) {
+                self.edit.set_message("Save or revert changes first".to_string(), MessageKind::Error);
+                return true;
+            }
+        }
+
+        // Edit tab lists only the programs of the library file.
+        let program_count = if self.current_tab == Tab::Edit {
+            programs.library_file_programs().map(|programs| programs.len()).unwrap_or(0)
+        } else {
+            programs.programs.len()
+        };
+
+        if program_count == 0 {
+            return update_ui;
+        }
+
+        // Library may have been reloaded with fewer programs or command queues.
+        if self.list_selection_index >= program_count {
+            self.list_selection_index = program_count - 1;
+            self.command_queue_i = 0;
+            update_ui = true;
+        }
+
+        if self.command_queue_i >= programs.programs[self.list_selection_index].command_queues.len() {
+            self.command_queue_i = 0;
+        }
 
         if input.down() {
             self.list_selection_index += 1;
 
-            if self.list_selection_index >= programs.programs.len() {
+            if self.list_selection_index >= program_count {
                 self.list_selection_index = 0;
             }
 
@@ -83,13 +275,29 @@ impl UiManager {
             if self.list_selection_index > 0 {
                 self.list_selection_index -= 1;
             } else {
-                self.list_selection_index = programs.programs.len() - 1;
+                self.list_selection_index = program_count - 1;
             }
 
             update_ui = true;
             self.command_queue_i = 0;
         }
 
+        if input.home() {
+            self.list_selection_index = 0;
+            update_ui = true;
+            self.command_queue_i = 0;
+        }
+
+        if input.end() {
+            self.list_selection_index = program_count - 1;
+            update_ui = true;
+            self.command_queue_i = 0;
+        }
+
+        if self.current_tab == Tab::Edit {
+            return update_ui;
+        }
+
         let command_queues = &programs.programs[self.list_selection_index].command_queues;
 
         if command_queues.len() == 0 {
@@ -122,9 +330,80 @@ impl UiManager {
         update_ui
     }
 
-    pub fn set_widgets<T: Window>(&mut self, task_manager: &mut TaskManager, programs: &ProgramLibrary, window: &mut T) {
-        set_widgets(self.ui.set_widgets(), &mut self.widget_ids, &mut self.list_selection_index, task_manager, programs, &self.console_text, window, &mut self.command_queue_i, self.launch_command_queue);
+    fn set_tab(&mut self, tab: Tab) {
+        if self.current_tab != tab {
+            self.current_tab = tab;
+            self.rebind_action = None;
+            self.edit.request_focus(FocusRequest::None);
+        }
+    }
+
+    pub fn set_widgets<T: Window>(&mut self, library: &mut ProgramLibraryManager, window: &mut T) {
+        if self.cancel_running_process {
+            library.task_manager_mut_and_programs().0.cancel();
+            self.cancel_running_process = false;
+        }
+
+        let previous_tab = self.current_tab;
+        let previous_theme = self.settings.theme;
+
+        let actions = {
+            let mut ui_cell = self.ui.set_widgets();
+            let ids = &mut self.widget_ids;
+            let palette = &self.palette;
+
+            set_tab_bar(&mut ui_cell, ids, &mut self.current_tab, palette);
+
+            match self.current_tab {
+                Tab::Library => {
+                    let (task_manager, programs) = library.task_manager_mut_and_programs();
+                    set_library_widgets(&mut ui_cell, ids, &mut self.list_selection_index, task_manager, programs, &mut self.console, &self.queue_status, &mut self.command_queue_i, self.launch_command_queue, palette);
+                    vec![]
+                }
+                Tab::Edit => edit::set_widgets(&mut ui_cell, ids, &mut self.edit, &mut self.list_selection_index, library, palette),
+                Tab::Settings => {
+                    let update = set_settings_widgets(&mut ui_cell, ids, &mut self.settings, &mut self.rebind_action, &mut self.key_binding_error, window, palette);
+
+                    if update.changed {
+                        self.settings_changed = true;
+                    }
+
+                    if update.unfocus {
+                        self.edit.request_focus(FocusRequest::None);
+                    }
+
+                    vec![]
+                }
+            }
+        };
+
+        if previous_theme != self.settings.theme {
+            self.palette = Palette::new(self.settings.theme);
+            self.ui.theme.label_color = self.palette.text;
+            self.ui.theme.shape_color = self.palette.button;
+            self.redraw = true;
+        }
+
         self.launch_command_queue = false;
+
+        if previous_tab != self.current_tab {
+            self.edit.request_focus(FocusRequest::None);
+            self.rebind_action = None;
+            self.redraw = true;
+        }
+
+        for action in actions {
+            self.edit.apply(action, library, &mut self.list_selection_index);
+            self.redraw = true;
+        }
+
+        let focused = self.ui.global_input().current.widget_capturing_keyboard;
+
+        if let Some(new_focus) = self.edit.take_focus_request(focused) {
+            let id = new_focus.unwrap_or(self.widget_ids.canvas);
+            self.ui.keyboard_capture(id);
+            self.redraw = true;
+        }
     }
 }
 
@@ -133,11 +412,14 @@ widget_ids! {
 
         canvas,
 
-        tabs,
+        // Tab bar
+        canvas_tab_bar,
+        tab_buttons[],
 
-        // Library tab
-        canvas_library,
+        // Content of the current tab
+        canvas_tab,
 
+        // Library tab
         canvas_library_layout,
 
         canvas_left,
@@ -146,10 +428,16 @@ widget_ids! {
         canvas_program_info,
         canvas_console,
 
-        console_text,
+        console_list,
+        toggle_stderr_only,
 
         program_title,
+        queue_status_text,
+        program_source_text,
         program_commands_list,
+        cancel_button,
+        command_results_text,
+        library_diagnostics_text,
 
         program_list,
 
@@ -158,80 +446,165 @@ widget_ids! {
 
         toggle_full_screen,
         text_full_screen,
-
+        drop_down_full_screen_monitor,
+        text_full_screen_monitor,
+        toggle_dark_theme,
+        text_console_scrollback,
+        number_dialer_console_scrollback,
+        text_library_directory,
+        text_box_library_directory,
+        text_key_bindings,
+        text_key_binding_error,
+        key_binding_texts[],
+        key_binding_buttons[],
+
+        // Edit tab
+        canvas_edit_layout,
+        canvas_edit_left,
+        canvas_edit_right,
+        canvas_edit_toolbar,
+        canvas_edit_form,
+
+        edit_program_list,
+        edit_add_program_button,
+        edit_save_button,
+        edit_revert_button,
+        edit_remove_program_button,
+        edit_message_text,
+
+        edit_labels[],
+        edit_text_boxes[],
+        edit_buttons[],
     }
 }
 
+// Wrapper type for Single. Disables key_selection method.
+struct ClickMode(Single);
+impl Mode for ClickMode {
+
+    type Selection = usize;
+
+    fn click_selection<F, D, S>(
+        &self,
+        c: Click,
+        i: usize,
+        num_items: usize,
+        state: &State,
+        is_selected: F,
+        pending: &mut PendingEvents<Self::Selection, D, S>
+    ) where
+        F: Fn(usize) -> bool {
+            self.0.click_selection(c, i, num_items, state, is_selected, pending);
+    }
 
+    fn key_selection<F, D, S>(
+        &self,
+        _press: KeyPress,
+        _i: usize,
+        _num_items: usize,
+        _state: &State,
+        _is_selected: F,
+        _pending: &mut PendingEvents<Self::Selection, D, S>
+    ) where
+        F: Fn(usize) -> bool,
+        D: Direction {
+        // Keyboard support disabled
+    }
 
-fn set_widgets<T: Window>(mut ui_cell: UiCell, ids: &mut WidgetIds, selection_i: &mut usize, task_manager: &mut TaskManager, program_library: &ProgramLibrary, console_text: &str, window: &mut T, command_queue_i: &mut usize, launch_command_queue: bool) {
-    use conrod::widget::{Canvas, Widget, Button, Text, ListSelect, List, Tabs, Toggle};
-    use conrod::{color, Colorable, Labelable, Positionable, Sizeable};
+}
 
-    use conrod::widget::list_select::{Mode, Event, State, PendingEvents, Direction, Single};
-    use conrod::event::{Click, KeyPress};
+/// Position of the tab in `TABS`.
+fn tab_index(tab: Tab) -> usize {
+    TABS.iter().position(|&(t, _)| t == tab).unwrap_or(0)
+}
 
-    // Wrapper type for Single. Disables key_selection method.
-    struct ClickMode(Single);
-    impl Mode for ClickMode {
+/// Returns next or previous tab.
+fn cycle_tab(tab: Tab, forward: bool) -> Tab {
+    let i = tab_index(tab);
 
-        type Selection = usize;
+    if forward {
+        TABS[(i + 1) % TABS.len()].0
+    } else {
+        TABS[(i + TABS.len() - 1) % TABS.len()].0
+    }
+}
 
-        fn click_selection<F, D, S>(
-            &self,
-            c: Click,
-            i: usize,
-            num_items: usize,
-            state: &State,
-            is_selected: F,
-            pending: &mut PendingEvents<Self::Selection, D, S>
-        ) where
-            F: Fn(usize) -> bool {
-                self.0.click_selection(c, i, num_items, state, is_selected, pending);
-        }
+/// Draws the tab bar and the canvas for the current tab.
+/// Tab can be changed with `Ctrl+Tab` and `Ctrl+Shift+Tab` in addition to the
+/// `next_tab` and `previous_tab` key bindings. These shortcuts are handled here,
+/// because key bindings don't have modifier keys, and they also work when
+/// a text field has keyboard focus.
+fn set_tab_bar(ui_cell: &mut UiCell, ids: &mut WidgetIds, current_tab: &mut Tab, palette: &Palette) {
+    use conrod::widget::{Canvas, Widget, Button};
+    use conrod::{Colorable, Labelable, Positionable, Sizeable};
+    use conrod::event;
+    use conrod::input::Key;
+    use conrod::input::keyboard::{CTRL, SHIFT};
+
+    Canvas::new()
+        .color(palette.background)
+        .flow_down(&[
+            (ids.canvas_tab_bar, Canvas::new().color(palette.tab_bar).length(TAB_BAR_HEIGHT)),
+            (ids.canvas_tab, Canvas::new().color(palette.background)),
+        ])
+        .set(ids.canvas, ui_cell);
 
-        fn key_selection<F, D, S>(
-            &self,
-            _press: KeyPress,
-            _i: usize,
-            _num_items: usize,
-            _state: &State,
-            _is_selected: F,
-            _pending: &mut PendingEvents<Self::Selection, D, S>
-        ) where
-            F: Fn(usize) -> bool,
-            D: Direction {
-            // Keyboard support disabled
+    for event in ui_cell.global_input().events().ui() {
+        if let event::Ui::Press(_, event::Press { button: event::Button::Keyboard(Key::Tab), modifiers }) = *event {
+            if modifiers.contains(CTRL) {
+                *current_tab = cycle_tab(*current_tab, !modifiers.contains(SHIFT));
+            }
         }
+    }
 
+    let mut tab_i = tab_index(*current_tab);
+
+    ids.tab_buttons.resize(TABS.len(), &mut ui_cell.widget_id_generator());
+
+    for (i, &(_, label)) in TABS.iter().enumerate() {
+        let color = if i == tab_i {
+            palette.button
+        } else {
+            palette.button_inactive
+        };
+
+        for _click in Button::new()
+            .label(label)
+            .label_color(palette.button_label)
+            .color(color)
+            .w_h(120.0, TAB_BAR_HEIGHT)
+            .top_left_with_margins_on(ids.canvas_tab_bar, 0.0, i as Scalar * 120.0)
+            .set(ids.tab_buttons[i], ui_cell)
+        {
+            tab_i = i;
+        }
     }
 
-    // UI layout
+    *current_tab = TABS[tab_i].0;
+}
 
-    Canvas::new()
-        .color(color::GREEN)
-        .set(ids.canvas, &mut ui_cell);
-
-    //Tabs::new(&[(ids.canvas_library, "Library"), (ids.canvas_settings, "Settings")])
-    Tabs::new(&[(ids.canvas_library, "Library")])
-        .starting_canvas(ids.canvas_library)
-        .middle_of(ids.canvas)
-        .wh_of(ids.canvas)
-        .color(color::LIGHT_GRAY)
-        .layout_horizontally()
-        .set(ids.tabs, &mut ui_cell);
+
+
+fn set_library_widgets(ui_cell: &mut UiCell, ids: &mut WidgetIds, selection_i: &mut usize, task_manager: &mut TaskManager, program_library: &ProgramLibrary, console: &mut ConsoleState, queue_status: &QueueStatus, command_queue_i: &mut usize, launch_command_queue: bool, palette: &Palette) {
+    use conrod::widget::{Canvas, Widget, Button, Text, ListSelect, List, Toggle};
+    use conrod::event;
+    use conrod::{Colorable, Labelable, Positionable, Sizeable};
+
+    use conrod::widget::list_select::Event;
+
+    // UI layout
 
     Canvas::new()
-        .top_left_of(ids.canvas_library)
-        .wh_of(ids.canvas_library)
+        .top_left_of(ids.canvas_tab)
+        .wh_of(ids.canvas_tab)
         .flow_right(&[
-            (ids.canvas_left, Canvas::new().color(color::LIGHT_BLUE).length(250.0)),
+            (ids.canvas_left, Canvas::new().color(palette.list_panel).length(250.0)),
             (ids.canvas_right, Canvas::new().flow_up(&[
-                (ids.canvas_console, Canvas::new().color(color::LIGHT_GREY).pad(10.0).scroll_kids_vertically().length(250.0)),
-                (ids.canvas_program_info, Canvas::new().pad(10.0).color(color::LIGHT_GRAY))
+                (ids.canvas_console, Canvas::new().color(palette.console).pad(10.0).crop_kids().length(250.0)),
+                (ids.canvas_program_info, Canvas::new().pad(10.0).color(palette.panel))
             ])),
         ])
-        .set(ids.canvas_library_layout, &mut ui_cell);
+        .set(ids.canvas_library_layout, ui_cell);
 
 
     // Program list
@@ -242,21 +615,21 @@ fn set_widgets<T: Window>(mut ui_cell: UiCell, ids: &mut WidgetIds, selection_i:
         .item_size(30.0)
         .wh_of(ids.canvas_left)
         .top_left_of(ids.canvas_left)
-        .set(ids.program_list, &mut ui_cell);
+        .set(ids.program_list, ui_cell);
 
-    while let Some(event) = events.next(&ui_cell, |i| i < program_library.programs.len()) {
+    while let Some(event) = events.next(ui_cell, |i| i < program_library.programs.len()) {
         match event {
             Event::Item(item) => {
                 let color = if item.i == *selection_i {
-                    color::LIGHT_GREEN
+                    palette.button_selected
                 } else {
-                    color::LIGHT_GRAY
+                    palette.button
                 };
                 let button = Button::new()
                     .color(color)
-                    .label_color(color::BLACK)
+                    .label_color(palette.button_label)
                     .label(&program_library.programs[item.i].name);
-                item.set(button, &mut ui_cell);
+                item.set(button, ui_cell);
             },
             Event::Selection(selection) => {
                 *selection_i = selection;
@@ -266,78 +639,293 @@ fn set_widgets<T: Window>(mut ui_cell: UiCell, ids: &mut WidgetIds, selection_i:
     }
 
     if let Some(s) = scrollbar {
-        s.set(&mut ui_cell);
+        s.set(ui_cell);
     }
 
     // Current program
 
-    let current_program = &program_library.programs[*selection_i];
+    let mut diagnostics = String::new();
 
-    Text::new(&current_program.name)
-        .top_left_of(ids.canvas_program_info)
-        .set(ids.program_title, &mut ui_cell);
+    for diagnostic in &program_library.diagnostics {
+        diagnostics.push_str(&diagnostic.to_string());
+        diagnostics.push('\n');
+    }
 
+    // Text widget below which command results are drawn.
+    let program_info_bottom = match program_library.programs.get(*selection_i) {
+        Some(current_program) => {
+            Text::new(&current_program.name)
+                .top_left_of(ids.canvas_program_info)
+                .set(ids.program_title, ui_cell);
+
+            let (mut items, scrollbar) = ListSelect::new(current_program.command_queues.len(), ClickMode(Single{}))
+                .flow_right()
+                .item_size(150.0)
+                .w_of(ids.canvas_program_info)
+                .h(40.0)
+                .down_from(ids.program_title, 10.0)
+                .set(ids.program_commands_list, ui_cell);
+
+            while let Some(event) = items.next(ui_cell, |i| i < current_program.command_queues.len()) {
+                match event {
+                    Event::Item(item) => {
+                        let current_command_queue = &current_program.command_queues[item.i];
+
+                        let color = if item.i == *command_queue_i {
+                            if launch_command_queue {
+                                task_manager.new_queue_if_no_running_process(current_program, current_command_queue);
+                            }
+                            palette.button_selected
+                        } else {
+                            palette.button
+                        };
+                        let button = Button::new()
+                            .color(color)
+                            .label_color(palette.button_label)
+                            .label(&current_command_queue.name);
+                        let button_event = item.set(button, ui_cell);
+
+                        for _click in button_event {
+                            task_manager.new_queue_if_no_running_process(current_program, current_command_queue);
+                        }
+                    },
+                    Event::Selection(selection) => {
+                        *command_queue_i = selection;
+                    },
+                    _ => (),
+                }
+            }
 
-    let (mut items, scrollbar) = ListSelect::new(current_program.command_queues.len(), ClickMode(Single{}))
-        .flow_right()
-        .item_size(150.0)
-        .w_of(ids.canvas_program_info)
-        .h(40.0)
-        .down_from(ids.program_title, 10.0)
-        .set(ids.program_commands_list, &mut ui_cell);
+            if let Some(s) = scrollbar {
+                s.set(ui_cell);
+            }
 
-    while let Some(event) = items.next(&ui_cell, |i| i < current_program.command_queues.len()) {
-        match event {
-            Event::Item(item) => {
-                let current_command_queue = &current_program.command_queues[item.i];
+            // Library file of the program
 
-                let color = if item.i == *command_queue_i {
-                    if launch_command_queue {
-                        task_manager.new_queue_if_no_running_process(&current_command_queue.commands, &current_program.working_directory, &current_program.download_command);
-                    }
-                    color::LIGHT_GREEN
-                } else {
-                    color::LIGHT_GRAY
-                };
-                let button = Button::new()
-                    .color(color)
-                    .label_color(color::BLACK)
-                    .label(&current_command_queue.name);
-                let button_event = item.set(button, &mut ui_cell);
+            Text::new(&format!("Source: {}", current_program.source.display()))
+                .down_from(ids.program_commands_list, 10.0)
+                .w_of(ids.canvas_program_info)
+                .font_size(12)
+                .color(palette.secondary_text)
+                .set(ids.program_source_text, ui_cell);
 
-                for _click in button_event {
-                    task_manager.new_queue_if_no_running_process(&current_command_queue.commands, &current_program.working_directory, &current_program.download_command);
-                }
-            },
-            Event::Selection(selection) => {
-                *command_queue_i = selection;
-            },
-            _ => (),
+            ids.program_source_text
         }
+        None => {
+            // Library file has errors or it is empty. Console and the cancel
+            // button are still drawn, because a command queue may be running.
+
+            let title = if program_library.diagnostics.is_empty() {
+                "Library is empty"
+            } else {
+                "Library file has errors"
+            };
+
+            Text::new(title)
+                .top_left_of(ids.canvas_program_info)
+                .set(ids.program_title, ui_cell);
+
+            ids.program_title
+        }
+    };
+
+    Text::new(&queue_status.text)
+        .right_from(ids.program_title, 20.0)
+        .color(palette.message(queue_status.kind))
+        .set(ids.queue_status_text, ui_cell);
+
+    let cancel_button_color = if task_manager.running() {
+        palette.button_cancel
+    } else {
+        palette.button_inactive
+    };
+
+    let cancel_events = Button::new()
+        .color(cancel_button_color)
+        .label_color(palette.button_label)
+        .label("Cancel")
+        .w_h(100.0, 30.0)
+        .top_right_of(ids.canvas_program_info)
+        .set(ids.cancel_button, ui_cell);
+
+    for _click in cancel_events {
+        task_manager.cancel();
     }
 
-    if let Some(s) = scrollbar {
-        s.set(&mut ui_cell);
+    // Results of finished commands
+
+    let mut command_results = String::new();
+
+    for result in task_manager.command_results() {
+        command_results.push_str(&result.to_string());
+        command_results.push('\n');
     }
 
+    Text::new(&command_results)
+        .down_from(program_info_bottom, 10.0)
+        .w_of(ids.canvas_program_info)
+        .font_size(12)
+        .set(ids.command_results_text, ui_cell);
+
+    // Library file errors and warnings
+
+    let diagnostics_color = if program_library.diagnostics.iter().any(Diagnostic::is_error) {
+        palette.error
+    } else {
+        palette.warning
+    };
+
+    Text::new(&diagnostics)
+        .down_from(ids.command_results_text, 10.0)
+        .w_of(ids.canvas_program_info)
+        .font_size(12)
+        .color(diagnostics_color)
+        .set(ids.library_diagnostics_text, ui_cell);
+
     // Console
 
+    for stderr_only in Toggle::new(console.stderr_only)
+        .label("Stderr only")
+        .label_color(palette.button_label)
+        .w_h(120.0, 30.0)
+        .color(palette.toggle)
+        .bottom_right_of(ids.canvas_program_info)
+        .set(ids.toggle_stderr_only, ui_cell)
+    {
+        console.stderr_only = stderr_only;
+        console.scroll_offset = 0;
+    }
+
+    let stderr_only = console.stderr_only;
+    let show_line = |line: &&ConsoleLine| !stderr_only || line.source == ConsoleLineSource::Stderr;
+
+    if let Some(console_rect) = ui_cell.rect_of(ids.canvas_console) {
+        let mouse_xy = ui_cell.global_input().current.mouse.xy;
+
+        for event in ui_cell.global_input().events().ui() {
+            if let &event::Ui::Scroll(_, ref scroll) = event {
+                if !console_rect.is_over(mouse_xy) {
+                    continue;
+                }
+
+                if scroll.y > 0.0 {
+                    console.scroll_offset = console.scroll_offset.saturating_sub(1);
+                } else if scroll.y < 0.0 {
+                    console.scroll_offset += 1;
+                }
+            }
+        }
+    }
+
+    let console_height = ui_cell.kid_area_of(ids.canvas_console).map(|rect| rect.h()).unwrap_or(0.0);
+    let visible_line_count = (console_height / CONSOLE_LINE_HEIGHT).max(0.0) as usize;
+    console.page_lines = visible_line_count;
+
+    let console_lines = task_manager.console_lines();
+
+    let line_count = if stderr_only {
+        console_lines.iter().filter(&show_line).count()
+    } else {
+        console_lines.len()
+    };
+
+    console.scroll_offset = console.scroll_offset.min(line_count.saturating_sub(visible_line_count));
+
+    // Only the lines which fit to the console are collected. Console lines are in ring buffer,
+    // so search starts from the newest line.
+    let mut visible_lines: Vec<&ConsoleLine> = console_lines
+        .iter()
+        .rev()
+        .filter(&show_line)
+        .skip(console.scroll_offset)
+        .take(visible_line_count)
+        .collect();
 
-    Text::new(console_text)
+    visible_lines.reverse();
+
+    let (mut items, _) = List::flow_down(visible_lines.len())
+        .item_size(CONSOLE_LINE_HEIGHT)
+        .kid_area_w_of(ids.canvas_console)
+        .h(visible_lines.len() as Scalar * CONSOLE_LINE_HEIGHT)
         .bottom_left_of(ids.canvas_console)
-        .w_of(ids.canvas_console)
-        .set(ids.console_text, &mut ui_cell);
+        .set(ids.console_list, ui_cell);
+
+    while let Some(item) = items.next(ui_cell) {
+        let line = visible_lines[item.i];
+
+        let color = match line.source {
+            ConsoleLineSource::Stdout => palette.stdout,
+            ConsoleLineSource::Stderr => palette.stderr,
+            ConsoleLineSource::Launcher => palette.launcher,
+        };
+
+        let text = Text::new(&line.text)
+            .color(color)
+            .font_size(12)
+            .no_line_wrap();
 
+        item.set(text, ui_cell);
+    }
+
+}
+
+/// Result of `set_settings_widgets`.
+struct SettingsUpdate {
+    changed: bool,
+    /// Remove keyboard focus from the text box.
+    unfocus: bool,
+}
+
+fn set_settings_widgets<T: Window>(ui_cell: &mut UiCell, ids: &mut WidgetIds, settings: &mut Settings, rebind_action: &mut Option<Action>, key_binding_error: &mut String, window: &mut T, palette: &Palette) -> SettingsUpdate {
+    use conrod::widget::{Canvas, Widget, Button, Text, TextBox, Toggle, NumberDialer, DropDownList};
+    use conrod::widget::text_box;
+    use conrod::{Colorable, Labelable, Positionable, Sizeable};
+    use conrod::event;
+    use conrod::input::Key;
 
-    // Settings
+    let mut update = SettingsUpdate {
+        changed: false,
+        unfocus: false,
+    };
+
+    for event in ui_cell.global_input().events().ui() {
+        if let event::Ui::Press(_, event::Press { button: event::Button::Keyboard(key), .. }) = *event {
+            if key == Key::Escape {
+                update.unfocus = true;
+            }
+
+            // Escape cancels key binding change.
+            if let Some(action) = rebind_action.take() {
+                if key != Key::Escape {
+                    match rebind_key(&mut settings.key_bindings, action, &key_name(key)) {
+                        Ok(()) => {
+                            key_binding_error.clear();
+                            update.changed = true;
+                        }
+                        Err(error) => *key_binding_error = error,
+                    }
+                }
+            }
+        }
+    }
+
+    Canvas::new()
+        .top_left_of(ids.canvas_tab)
+        .wh_of(ids.canvas_tab)
+        .pad(20.0)
+        .color(palette.panel)
+        .scroll_kids_vertically()
+        .set(ids.canvas_settings, ui_cell);
+
+    // Full screen
 
     let event = Toggle::new(window.full_screen())
         .label("Full screen mode")
-        .label_color(color::WHITE)
+        .label_color(palette.button_label)
         .w_h(150.0, 50.0)
-        .color(color::LIGHT_BLUE)
-        .top_left_with_margin_on(ids.canvas_settings, 20.0)
-        .set(ids.toggle_full_screen, &mut ui_cell);
+        .color(palette.toggle)
+        .top_left_of(ids.canvas_settings)
+        .set(ids.toggle_full_screen, ui_cell);
 
     let text = if window.full_screen() {
         "Enabled"
@@ -347,10 +935,151 @@ fn set_widgets<T: Window>(mut ui_cell: UiCell, ids: &mut WidgetIds, selection_i:
 
     Text::new(text)
         .right_from(ids.toggle_full_screen, 20.0)
-        .set(ids.text_full_screen, &mut ui_cell);
+        .set(ids.text_full_screen, ui_cell);
 
     for new_state in event {
         window.set_full_screen(new_state);
+        settings.full_screen = new_state;
+        update.changed = true;
     }
-}
 
+    // Full screen monitor
+
+    let mut monitors = vec!["Primary monitor".to_string()];
+    monitors.extend(window.monitor_names());
+
+    let selected_monitor = match window.full_screen_monitor() {
+        Some(i) => i + 1,
+        None => 0,
+    };
+
+    for monitor_i in DropDownList::new(&monitors, Some(selected_monitor))
+        .w_h(300.0, 40.0)
+        .max_visible_items(5)
+        .label_color(palette.button_label)
+        .color(palette.toggle)
+        .down_from(ids.toggle_full_screen, 20.0)
+        .set(ids.drop_down_full_screen_monitor, ui_cell)
+    {
+        let monitor = if monitor_i == 0 {
+            None
+        } else {
+            Some(monitor_i - 1)
+        };
+
+        window.set_full_screen_monitor(monitor);
+        settings.full_screen_monitor = monitor;
+        update.changed = true;
+    }
+
+    Text::new("Full screen monitor")
+        .right_from(ids.drop_down_full_screen_monitor, 20.0)
+        .set(ids.text_full_screen_monitor, ui_cell);
+
+    // Theme
+
+    for dark_theme in Toggle::new(settings.theme == Theme::Dark)
+        .label("Dark theme")
+        .label_color(palette.button_label)
+        .w_h(150.0, 50.0)
+        .color(palette.toggle)
+        .down_from(ids.drop_down_full_screen_monitor, 20.0)
+        .set(ids.toggle_dark_theme, ui_cell)
+    {
+        settings.theme = if dark_theme {
+            Theme::Dark
+        } else {
+            Theme::Light
+        };
+        update.changed = true;
+    }
+
+    // Console scrollback
+
+    Text::new("Console scrollback lines")
+        .down_from(ids.toggle_dark_theme, 30.0)
+        .set(ids.text_console_scrollback, ui_cell);
+
+    if let Some(value) = NumberDialer::new(settings.console_scrollback_lines as f64, 100.0, 100_000.0, 0)
+        .w_h(150.0, 40.0)
+        .right_from(ids.text_console_scrollback, 20.0)
+        .set(ids.number_dialer_console_scrollback, ui_cell)
+    {
+        settings.console_scrollback_lines = value as usize;
+        update.changed = true;
+    }
+
+    // Library directory
+
+    Text::new("Library directory (used after restart, empty for the default directory)")
+        .down_from(ids.text_console_scrollback, 40.0)
+        .set(ids.text_library_directory, ui_cell);
+
+    let text_box_width = ui_cell.kid_area_of(ids.canvas_settings).map(|rect| rect.w()).unwrap_or(400.0);
+
+    for event in TextBox::new(&settings.library_directory)
+        .font_size(14)
+        .w_h(text_box_width, 30.0)
+        .down_from(ids.text_library_directory, 10.0)
+        .set(ids.text_box_library_directory, ui_cell)
+    {
+        match event {
+            text_box::Event::Update(text) => {
+                settings.library_directory = text;
+                update.changed = true;
+            }
+            text_box::Event::Enter => update.unfocus = true,
+        }
+    }
+
+    // Key bindings
+
+    Text::new("Key bindings (Change replaces the first key, press Escape to cancel key binding change)")
+        .down_from(ids.text_box_library_directory, 30.0)
+        .set(ids.text_key_bindings, ui_cell);
+
+    Text::new(key_binding_error)
+        .right_from(ids.text_key_bindings, 20.0)
+        .color(palette.error)
+        .set(ids.text_key_binding_error, ui_cell);
+
+    if ids.key_binding_texts.len() != Action::ALL.len() {
+        ids.key_binding_texts.resize(Action::ALL.len(), &mut ui_cell.widget_id_generator());
+        ids.key_binding_buttons.resize(Action::ALL.len(), &mut ui_cell.widget_id_generator());
+    }
+
+    for (i, &action) in Action::ALL.iter().enumerate() {
+        let previous_id = if i == 0 {
+            ids.text_key_bindings
+        } else {
+            ids.key_binding_buttons[i - 1]
+        };
+
+        let label = if *rebind_action == Some(action) {
+            "Press a key"
+        } else {
+            "Change"
+        };
+
+        for _click in Button::new()
+            .label(label)
+            .w_h(120.0, 30.0)
+            .down_from(previous_id, 10.0)
+            .align_left_of(ids.text_key_bindings)
+            .set(ids.key_binding_buttons[i], ui_cell)
+        {
+            *rebind_action = Some(action);
+        }
+
+        let keys = match settings.key_bindings.get(action.name()) {
+            Some(keys) => keys.join(", "),
+            None => String::new(),
+        };
+
+        Text::new(&format!("{}: {}", action.name(), keys))
+            .right_from(ids.key_binding_buttons[i], 20.0)
+            .set(ids.key_binding_texts[i], ui_cell);
+    }
+
+    update
+}
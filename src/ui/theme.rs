@@ -0,0 +1,111 @@
+//! Colors of the UI themes.
+
+use conrod::{Color, color};
+
+use settings::Theme;
+
+/// Kind of a status message. Color of the message depends on the theme.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageKind {
+    Normal,
+    Info,
+    Running,
+    Success,
+    Warning,
+    Error,
+}
+
+pub struct Palette {
+    pub background: Color,
+    pub tab_bar: Color,
+    pub panel: Color,
+    /// Background of program lists.
+    pub list_panel: Color,
+    pub console: Color,
+    pub text: Color,
+    /// Less important text, for example the library file of a program.
+    pub secondary_text: Color,
+    pub stdout: Color,
+    pub stderr: Color,
+    pub launcher: Color,
+    /// Buttons, list items and the current tab.
+    pub button: Color,
+    /// Selected list item and button which has an effect, for example Save.
+    pub button_selected: Color,
+    /// Other tabs and buttons which don't have an effect.
+    pub button_inactive: Color,
+    /// Cancel button when there is a running process.
+    pub button_cancel: Color,
+    pub button_label: Color,
+    /// Toggles and drop down lists.
+    pub toggle: Color,
+    pub info: Color,
+    pub running: Color,
+    pub success: Color,
+    pub warning: Color,
+    pub error: Color,
+}
+
+impl Palette {
+    pub fn new(theme: Theme) -> Palette {
+        match theme {
+            Theme::Light => Palette {
+                background: color::LIGHT_GRAY,
+                tab_bar: color::DARK_GRAY,
+                panel: color::LIGHT_GRAY,
+                list_panel: color::LIGHT_BLUE,
+                console: color::LIGHT_GREY,
+                text: color::BLACK,
+                secondary_text: color::DARK_GRAY,
+                stdout: color::BLACK,
+                stderr: color::DARK_RED,
+                launcher: color::DARK_BLUE,
+                button: color::LIGHT_GRAY,
+                button_selected: color::LIGHT_GREEN,
+                button_inactive: color::GRAY,
+                button_cancel: color::LIGHT_RED,
+                button_label: color::BLACK,
+                toggle: color::LIGHT_BLUE,
+                info: color::DARK_BLUE,
+                running: color::DARK_YELLOW,
+                success: color::DARK_GREEN,
+                warning: color::DARK_ORANGE,
+                error: color::DARK_RED,
+            },
+            Theme::Dark => Palette {
+                background: color::DARK_CHARCOAL,
+                tab_bar: color::BLACK,
+                panel: color::CHARCOAL,
+                list_panel: color::DARK_BLUE,
+                console: color::DARK_CHARCOAL,
+                text: color::WHITE,
+                secondary_text: color::GRAY,
+                stdout: color::LIGHT_GREY,
+                stderr: color::LIGHT_RED,
+                launcher: color::LIGHT_BLUE,
+                button: color::DARK_GRAY,
+                button_selected: color::DARK_GREEN,
+                button_inactive: color::DARK_CHARCOAL,
+                button_cancel: color::DARK_RED,
+                button_label: color::WHITE,
+                toggle: color::BLUE,
+                info: color::LIGHT_BLUE,
+                running: color::LIGHT_YELLOW,
+                success: color::LIGHT_GREEN,
+                warning: color::LIGHT_ORANGE,
+                error: color::LIGHT_RED,
+            },
+        }
+    }
+
+    pub fn message(&self, kind: MessageKind) -> Color {
+        match kind {
+            MessageKind::Normal => self.text,
+            MessageKind::Info => self.info,
+            MessageKind::Running => self.running,
+            MessageKind::Success => self.success,
+            MessageKind::Warning => self.warning,
+            MessageKind::Error => self.error,
+        }
+    }
+}
@@ -230,6 +230,12 @@ impl TimeMilliseconds {
     fn clone(&self) -> TimeMilliseconds {
         TimeMilliseconds(self.0)
     }
+
+    /// Fake time for tests.
+    #[cfg(test)]
+    pub fn from_milliseconds(milliseconds: u32) -> TimeMilliseconds {
+        TimeMilliseconds(milliseconds)
+    }
 }
 
 /// Check time between updates.
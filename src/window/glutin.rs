@@ -5,7 +5,7 @@ use gilrs;
 
 use conrod::position::Scalar;
 use conrod::Ui;
-use conrod::backend::winit::{convert_event, WinitWindow};
+use conrod::backend::winit::{convert_event, map_key, WinitWindow};
 
 use self::glium::backend::{Facade, Context};
 use self::glium::{Display, Frame};
@@ -14,7 +14,7 @@ use gilrs::{Gilrs};
 
 use std::rc::Rc;
 
-use window::Window;
+use window::{Window, OpenGLWindow};
 use input::InputUpdater;
 use utils::TimeMilliseconds;
 
@@ -22,19 +22,26 @@ use self::glutin::{
     WindowBuilder,
     Event,
     KeyboardInput,
-    VirtualKeyCode,
     WindowEvent,
     EventsLoop,
     ContextBuilder,
     GlRequest,
     Api,
+    MonitorId,
 };
 
 
 pub struct GlutinWindow {
     display: Display,
     event_loop: EventsLoop,
+    title: String,
     full_screen: bool,
+    /// Index of the full screen monitor. `None` is the primary monitor.
+    full_screen_monitor: Option<usize>,
+    /// Window size which is restored when leaving full screen mode.
+    windowed_size: (u32, u32),
+    /// Window position which is restored when leaving full screen mode.
+    windowed_position: Option<(i32, i32)>,
     window_focused: bool,
     game_controllers: Gilrs,
 }
@@ -43,30 +50,58 @@ impl GlutinWindow {
     pub fn display(&self) -> &Display {
         &self.display
     }
-}
 
-impl Window for GlutinWindow {
-    fn new(title: &str, width: u32, height: u32, full_screen: bool) -> Self {
-        let event_loop = EventsLoop::new();
+    /// Saves current window size and position if window is not in full screen mode.
+    fn save_windowed_geometry(&mut self) {
+        if self.full_screen {
+            return;
+        }
+
+        let window = self.display.gl_window();
+
+        if let Some(size) = window.get_inner_size() {
+            self.windowed_size = size;
+        }
+
+        self.windowed_position = window.get_position();
+    }
+
+    /// Creates a new window with the same OpenGL context.
+    fn rebuild(&mut self, full_screen: bool) {
+        let window = window_builder(&self.title, full_screen, self.full_screen_monitor, self.windowed_size);
 
-        let mut window = WindowBuilder::new()
-            .with_title(title);
+        match self.display.rebuild(window, context_builder(), &self.event_loop) {
+            Ok(()) => self.full_screen = full_screen,
+            Err(error) => {
+                println!("window rebuild error: {}", error);
+                return;
+            }
+        }
 
-        if full_screen {
-            window = window.with_fullscreen(glutin::get_primary_monitor());
-        } else {
-            window = window.with_dimensions(width, height);
+        if !full_screen {
+            if let Some((x, y)) = self.windowed_position {
+                self.display.gl_window().set_position(x, y);
+            }
         }
+    }
+}
+
+impl Window for GlutinWindow {
+    fn new(title: &str, width: u32, height: u32, full_screen: bool, full_screen_monitor: Option<usize>) -> Self {
+        let event_loop = EventsLoop::new();
 
-        let context = ContextBuilder::new()
-            .with_gl(GlRequest::Specific(Api::OpenGlEs, (2,0)));
+        let window = window_builder(title, full_screen, full_screen_monitor, (width, height));
 
-        let display = Display::new(window, context, &event_loop).expect("error");
+        let display = Display::new(window, context_builder(), &event_loop).expect("error");
 
         GlutinWindow {
             display,
             event_loop,
+            title: title.to_string(),
             full_screen,
+            full_screen_monitor,
+            windowed_size: (width, height),
+            windowed_position: None,
             window_focused: true,
             game_controllers: Gilrs::new(),
         }
@@ -101,8 +136,12 @@ impl Window for GlutinWindow {
 
         self.window_focused = window_focused;
 
-        for (_, e) in self.game_controllers.poll_events() {
-            if self.window_focused && handle_game_controller_input(update, e, current_time) {
+        let events: Vec<(usize, gilrs::Event)> = self.game_controllers.poll_events().collect();
+
+        for (id, e) in events {
+            let controller_name = self.game_controllers[id].name().to_string();
+
+            if self.window_focused && handle_game_controller_input(update, id, &controller_name, e, current_time) {
                 event_update = true;
             }
         }
@@ -110,21 +149,44 @@ impl Window for GlutinWindow {
         return event_update;
     }
 
-    fn draw(&mut self) -> Frame {
-        self.display.draw()
-    }
-
     fn full_screen(&self) -> bool {
         self.full_screen
     }
 
     fn set_full_screen(&mut self, value: bool) {
-        self.full_screen = value;
-        // TODO: full screen support
+        if self.full_screen == value {
+            return;
+        }
+
+        self.save_windowed_geometry();
+        self.rebuild(value);
     }
 
-    fn opengl_version(&self) -> &glium::Version {
-        self.display.get_opengl_version()
+    fn monitor_names(&self) -> Vec<String> {
+        glutin::get_available_monitors()
+            .enumerate()
+            .map(|(i, monitor)| {
+                let (width, height) = monitor.get_dimensions();
+                let name = monitor.get_name().unwrap_or_else(|| format!("Monitor {}", i + 1));
+                format!("{} ({}x{})", name, width, height)
+            })
+            .collect()
+    }
+
+    fn full_screen_monitor(&self) -> Option<usize> {
+        self.full_screen_monitor
+    }
+
+    fn set_full_screen_monitor(&mut self, monitor: Option<usize>) {
+        if self.full_screen_monitor == monitor {
+            return;
+        }
+
+        self.full_screen_monitor = monitor;
+
+        if self.full_screen {
+            self.rebuild(true);
+        }
     }
 
     fn width_and_height(&self) -> (u32, u32) {
@@ -136,86 +198,65 @@ impl Window for GlutinWindow {
     }
 }
 
+impl OpenGLWindow for GlutinWindow {
+    fn draw(&mut self) -> Frame {
+        self.display.draw()
+    }
+
+    fn opengl_version(&self) -> &glium::Version {
+        self.display.get_opengl_version()
+    }
+}
+
 impl Facade for GlutinWindow {
     fn get_context(&self) -> &Rc<Context> {
         self.display.get_context()
     }
 }
 
-/// Returns true if InputUpdater was updated.
-fn handle_keyboard_input<T: InputUpdater>(update: &mut T, keyboard_input: KeyboardInput, current_time: &TimeMilliseconds) -> bool {
-    use input::utils::KeyEvent;
-    use self::glutin::ElementState;
+/// Returns monitor at `index` or the primary monitor if there is no such monitor.
+fn monitor(index: Option<usize>) -> MonitorId {
+    index
+        .and_then(|i| glutin::get_available_monitors().nth(i))
+        .unwrap_or_else(glutin::get_primary_monitor)
+}
 
-    let mut updated_input = true;
+fn window_builder(title: &str, full_screen: bool, full_screen_monitor: Option<usize>, windowed_size: (u32, u32)) -> WindowBuilder {
+    let window = WindowBuilder::new()
+        .with_title(title);
 
-    match keyboard_input {
-        KeyboardInput {virtual_keycode: Some(VirtualKeyCode::Escape), ..} => update.set_quit(true),
-        KeyboardInput {virtual_keycode: Some(VirtualKeyCode::Return), state: ElementState::Released, ..} => update.set_select(true),
-        KeyboardInput {virtual_keycode: Some(VirtualKeyCode::Left), state: ElementState::Pressed, ..} => update.update_left(KeyEvent::KeyDown, current_time),
-        KeyboardInput {virtual_keycode: Some(VirtualKeyCode::Left), state: ElementState::Released, ..} => update.update_left(KeyEvent::KeyUp, current_time),
-        KeyboardInput {virtual_keycode: Some(VirtualKeyCode::Right), state: ElementState::Pressed, ..} => update.update_right(KeyEvent::KeyDown, current_time),
-        KeyboardInput {virtual_keycode: Some(VirtualKeyCode::Right), state: ElementState::Released, ..} => update.update_right(KeyEvent::KeyUp, current_time),
-        KeyboardInput {virtual_keycode: Some(VirtualKeyCode::Up), state: ElementState::Pressed, ..} => update.update_up(KeyEvent::KeyDown, current_time),
-        KeyboardInput {virtual_keycode: Some(VirtualKeyCode::Up), state: ElementState::Released, ..} => update.update_up(KeyEvent::KeyUp, current_time),
-        KeyboardInput {virtual_keycode: Some(VirtualKeyCode::Down), state: ElementState::Pressed, ..} => update.update_down(KeyEvent::KeyDown, current_time),
-        KeyboardInput {virtual_keycode: Some(VirtualKeyCode::Down), state: ElementState::Released, ..} => update.update_down(KeyEvent::KeyUp, current_time),
-        _ => updated_input = false,
+    if full_screen {
+        window.with_fullscreen(monitor(full_screen_monitor))
+    } else {
+        window.with_dimensions(windowed_size.0, windowed_size.1)
     }
+}
 
-    updated_input
+fn context_builder() -> ContextBuilder<'static> {
+    ContextBuilder::new()
+        .with_gl(GlRequest::Specific(Api::OpenGlEs, (2,0)))
 }
 
 /// Returns true if InputUpdater was updated.
-fn handle_game_controller_input<T: InputUpdater>(update: &mut T, event: gilrs::Event, current_time: &TimeMilliseconds) -> bool {
-    let mut updated_input = true;
+fn handle_keyboard_input<T: InputUpdater>(update: &mut T, keyboard_input: KeyboardInput, current_time: &TimeMilliseconds) -> bool {
+    use self::glutin::ElementState;
 
-    use gilrs::Event::{ButtonReleased, ButtonPressed, AxisChanged};
-    use gilrs::Button::{South, DPadUp, DPadDown, DPadLeft, DPadRight};
+    let key = match keyboard_input.virtual_keycode {
+        Some(virtual_keycode) => map_key(virtual_keycode),
+        None => return false,
+    };
 
-    use gilrs::Axis::{LeftStickX, LeftStickY};
+    update.key_input(key, keyboard_input.state == ElementState::Pressed, current_time)
+}
 
-    use input::utils::KeyEvent::{KeyDown, KeyUp};
+/// Returns true if InputUpdater was updated.
+fn handle_game_controller_input<T: InputUpdater>(update: &mut T, controller_id: usize, controller_name: &str, event: gilrs::Event, current_time: &TimeMilliseconds) -> bool {
+    use gilrs::Event::{ButtonReleased, ButtonPressed, AxisChanged};
 
     match event {
-        ButtonReleased(South, _)     => update.set_select(true),
-
-        ButtonPressed(DPadLeft, _)  => update.update_left(KeyDown, current_time),
-        ButtonPressed(DPadRight, _) => update.update_right(KeyDown, current_time),
-        ButtonPressed(DPadUp,_)     => update.update_up(KeyDown, current_time),
-        ButtonPressed(DPadDown,_)   => update.update_down(KeyDown, current_time),
-
-        ButtonReleased(DPadLeft, _)   => update.update_left(KeyUp, current_time),
-        ButtonReleased(DPadRight, _)  => update.update_right(KeyUp, current_time),
-        ButtonReleased(DPadUp, _)     => update.update_up(KeyUp, current_time),
-        ButtonReleased(DPadDown, _)   => update.update_down(KeyUp, current_time),
-
-        AxisChanged(LeftStickX, value, _) => {
-            let limit = 0.3;
-            if value >= limit {
-                update.update_right(KeyDown, current_time);
-            } else if value <= -limit {
-                update.update_left(KeyDown, current_time);
-            } else {
-                update.update_left(KeyUp, current_time);
-                update.update_right(KeyUp, current_time);
-            }
-        }
-
-        AxisChanged(LeftStickY, value, _) => {
-            let limit = 0.3;
-            if value >= limit {
-                update.update_up(KeyDown, current_time);
-            } else if value <= -limit {
-                update.update_down(KeyDown, current_time);
-            } else {
-                update.update_up(KeyUp, current_time);
-                update.update_down(KeyUp, current_time);
-            }
-        }
-        _ => updated_input = false,
+        ButtonPressed(button, _) => update.game_controller_button(controller_name, &format!("{:?}", button), true, current_time),
+        ButtonReleased(button, _) => update.game_controller_button(controller_name, &format!("{:?}", button), false, current_time),
+        AxisChanged(axis, value, _) => update.game_controller_axis(controller_id, controller_name, &format!("{:?}", axis), value, current_time),
+        _ => false,
     }
-
-    updated_input
 }
-
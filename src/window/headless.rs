@@ -0,0 +1,137 @@
+//! Window without a display for testing the UI.
+//!
+//! Input is simulated by pushing events to the window. Events are
+//! handled when `update_input` is called.
+
+use conrod::position::Scalar;
+use conrod::Ui;
+use conrod::event::Input;
+
+use std::collections::VecDeque;
+
+use window::Window;
+use input::InputUpdater;
+use input::utils::KeyEvent;
+use utils::TimeMilliseconds;
+
+/// Simulated input event.
+#[derive(Clone)]
+pub enum HeadlessEvent {
+    /// Conrod input, for example mouse or text input for widgets.
+    Ui(Input),
+    Quit,
+    Select,
+    Cancel,
+    Up(KeyEvent),
+    Down(KeyEvent),
+    Left(KeyEvent),
+    Right(KeyEvent),
+    Back,
+    PageUp(KeyEvent),
+    PageDown(KeyEvent),
+    Home,
+    End,
+    NextTab,
+    PreviousTab,
+}
+
+pub struct HeadlessWindow {
+    events: VecDeque<HeadlessEvent>,
+    width: u32,
+    height: u32,
+    full_screen: bool,
+    full_screen_monitor: Option<usize>,
+}
+
+impl HeadlessWindow {
+    /// Adds event which will be handled at next `update_input` call.
+    pub fn push_event(&mut self, event: HeadlessEvent) {
+        self.events.push_back(event);
+    }
+
+    /// Simulates key press and release.
+    pub fn push_key_hit(&mut self, event: fn(KeyEvent) -> HeadlessEvent) {
+        self.push_event(event(KeyEvent::KeyDown));
+        self.push_event(event(KeyEvent::KeyUp));
+    }
+
+    /// Resizes the window and sends the new size to the UI.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.push_event(HeadlessEvent::Ui(Input::Resize(width, height)));
+    }
+}
+
+impl Window for HeadlessWindow {
+    fn new(_title: &str, width: u32, height: u32, full_screen: bool, full_screen_monitor: Option<usize>) -> Self {
+        let mut window = HeadlessWindow {
+            events: VecDeque::new(),
+            width,
+            height,
+            full_screen,
+            full_screen_monitor,
+        };
+
+        // UI is created with the default window size.
+        window.resize(width, height);
+
+        window
+    }
+
+    fn update_input<T: InputUpdater>(&mut self, update: &mut T, ui: &mut Ui, current_time: &TimeMilliseconds) -> bool {
+        let mut event_update = false;
+
+        while let Some(event) = self.events.pop_front() {
+            match event {
+                HeadlessEvent::Ui(input) => ui.handle_event(input),
+                HeadlessEvent::Quit => update.set_quit(true),
+                HeadlessEvent::Select => update.set_select(true),
+                HeadlessEvent::Cancel => update.set_cancel(true),
+                HeadlessEvent::Up(key_event) => update.update_up(key_event, current_time),
+                HeadlessEvent::Down(key_event) => update.update_down(key_event, current_time),
+                HeadlessEvent::Left(key_event) => update.update_left(key_event, current_time),
+                HeadlessEvent::Right(key_event) => update.update_right(key_event, current_time),
+                HeadlessEvent::Back => update.set_back(true),
+                HeadlessEvent::PageUp(key_event) => update.update_page_up(key_event, current_time),
+                HeadlessEvent::PageDown(key_event) => update.update_page_down(key_event, current_time),
+                HeadlessEvent::Home => update.set_home(true),
+                HeadlessEvent::End => update.set_end(true),
+                HeadlessEvent::NextTab => update.set_next_tab(true),
+                HeadlessEvent::PreviousTab => update.set_previous_tab(true),
+            }
+
+            event_update = true;
+        }
+
+        event_update
+    }
+
+    fn full_screen(&self) -> bool {
+        self.full_screen
+    }
+
+    fn set_full_screen(&mut self, value: bool) {
+        self.full_screen = value;
+    }
+
+    fn monitor_names(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn full_screen_monitor(&self) -> Option<usize> {
+        self.full_screen_monitor
+    }
+
+    fn set_full_screen_monitor(&mut self, monitor: Option<usize>) {
+        self.full_screen_monitor = monitor;
+    }
+
+    fn width_and_height(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn dpi_factor(&self) -> Scalar {
+        1.0
+    }
+}
@@ -6,6 +6,8 @@ pub mod sdl2;
 #[cfg(feature = "glutin-window")]
 pub mod glutin;
 
+pub mod headless;
+
 use conrod::Ui;
 use input::InputUpdater;
 
@@ -16,18 +18,33 @@ use conrod::backend::glium::glium::backend::Facade;
 
 use utils::TimeMilliseconds;
 
-pub trait Window : Facade {
-    fn new(title: &str, width: u32, height: u32, full_screen: bool) -> Self;
+pub trait Window {
+    /// Creates a new window. Width and height are the window size when the window is not in full screen mode.
+    fn new(title: &str, width: u32, height: u32, full_screen: bool, full_screen_monitor: Option<usize>) -> Self;
 
     /// Returns true if ui or InputUpdater was updated.
     fn update_input<T: InputUpdater>(&mut self, update: &mut T, ui: &mut Ui, current_time: &TimeMilliseconds) -> bool;
-    fn draw(&mut self) -> Frame;
 
     fn full_screen(&self) -> bool;
 
+    /// Switches between windowed and full screen mode. Window size and position
+    /// are restored when leaving full screen mode.
     fn set_full_screen(&mut self, value: bool);
 
-    fn opengl_version(&self) -> &Version;
+    /// Names of available monitors. Monitor index is the index of the name.
+    fn monitor_names(&self) -> Vec<String>;
+
+    /// Monitor for full screen mode. `None` is the primary monitor.
+    fn full_screen_monitor(&self) -> Option<usize>;
+
+    fn set_full_screen_monitor(&mut self, monitor: Option<usize>);
+
     fn width_and_height(&self) -> (u32, u32);
     fn dpi_factor(&self) -> Scalar;
-}
\ No newline at end of file
+}
+
+/// Window with an OpenGL context for glium.
+pub trait OpenGLWindow : Window + Facade {
+    fn draw(&mut self) -> Frame;
+    fn opengl_version(&self) -> &Version;
+}
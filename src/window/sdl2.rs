@@ -1,4 +1,408 @@
+use conrod::backend::glium::glium;
+use sdl2;
 
+use conrod::position::Scalar;
+use conrod::Ui;
+use conrod::event::Input;
+use conrod::input::{self, Motion};
+use conrod::input::keyboard::Key;
 
+use self::glium::backend::{Backend, Context, Facade};
+use self::glium::debug::DebugCallbackBehavior;
+use self::glium::{Frame, SwapBuffersError};
 
+use sdl2::{Sdl, EventPump, GameControllerSubsystem};
+use sdl2::controller::{GameController, Axis, Button};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::video::{self, GLContext, GLProfile, FullscreenType, WindowPos};
 
+use std::cell::RefCell;
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+use window::{Window, OpenGLWindow};
+use input::InputUpdater;
+use utils::TimeMilliseconds;
+
+pub struct SDL2Window {
+    _sdl_context: Sdl,
+    event_pump: EventPump,
+    window: Rc<RefCell<video::Window>>,
+    context: Rc<Context>,
+    full_screen: bool,
+    /// Index of the full screen monitor. `None` is the primary monitor.
+    full_screen_monitor: Option<usize>,
+    /// Window size which is restored when leaving full screen mode.
+    windowed_size: (u32, u32),
+    /// Window position which is restored when leaving full screen mode.
+    windowed_position: Option<(i32, i32)>,
+    window_focused: bool,
+    game_controller_subsystem: GameControllerSubsystem,
+    /// Game controllers must be kept open to receive events from them.
+    game_controllers: Vec<GameController>,
+}
+
+impl SDL2Window {
+    /// Opens game controller if joystick at `joystick_index` is a game controller.
+    fn open_game_controller(&mut self, joystick_index: u32) {
+        if !self.game_controller_subsystem.is_game_controller(joystick_index) {
+            return;
+        }
+
+        match self.game_controller_subsystem.open(joystick_index) {
+            Ok(controller) => self.game_controllers.push(controller),
+            Err(error) => println!("game controller error: {}", error),
+        }
+    }
+
+    /// Switches window to full screen mode or back to windowed mode.
+    fn apply_full_screen(&mut self, full_screen: bool) {
+        let mut window = self.window.borrow_mut();
+
+        let result = if full_screen {
+            // Desktop full screen uses the display of the window, so move the
+            // window to the selected display first. `None` is display 0.
+            let display_index = self.full_screen_monitor.unwrap_or(0) as i32;
+
+            match window.subsystem().display_bounds(display_index) {
+                Ok(bounds) => window.set_position(WindowPos::Positioned(bounds.x()), WindowPos::Positioned(bounds.y())),
+                Err(error) => println!("display bounds error: {}", error),
+            }
+
+            window.set_fullscreen(FullscreenType::Desktop)
+        } else {
+            window.set_fullscreen(FullscreenType::Off)
+        };
+
+        if let Err(error) = result {
+            println!("full screen mode change error: {}", error);
+            return;
+        }
+
+        self.full_screen = full_screen;
+
+        if !full_screen {
+            if let Err(error) = window.set_size(self.windowed_size.0, self.windowed_size.1) {
+                println!("window size error: {}", error);
+            }
+
+            if let Some((x, y)) = self.windowed_position {
+                window.set_position(WindowPos::Positioned(x), WindowPos::Positioned(y));
+            }
+        }
+    }
+}
+
+impl Window for SDL2Window {
+    fn new(title: &str, width: u32, height: u32, full_screen: bool, full_screen_monitor: Option<usize>) -> Self {
+        let sdl_context = sdl2::init().expect("SDL2 initialization error");
+        let video_subsystem = sdl_context.video().expect("SDL2 video subsystem error");
+
+        {
+            let gl_attr = video_subsystem.gl_attr();
+            gl_attr.set_context_profile(GLProfile::GLES);
+            gl_attr.set_context_version(2, 0);
+        }
+
+        let sdl_window = video_subsystem
+            .window(title, width, height)
+            .opengl()
+            .resizable()
+            .build()
+            .expect("window creation error");
+
+        let gl_context = sdl_window.gl_create_context().expect("OpenGL context creation error");
+
+        let window = Rc::new(RefCell::new(sdl_window));
+
+        let backend = SDL2Backend {
+            window: window.clone(),
+            gl_context,
+        };
+
+        let context = unsafe {
+            Context::new(backend, false, DebugCallbackBehavior::default())
+        }.expect("OpenGL context error");
+
+        let event_pump = sdl_context.event_pump().expect("SDL2 event pump error");
+        let game_controller_subsystem = sdl_context.game_controller().expect("SDL2 game controller subsystem error");
+
+        // Text input events are needed for conrod's text boxes.
+        video_subsystem.text_input().start();
+
+        let mut sdl2_window = SDL2Window {
+            _sdl_context: sdl_context,
+            event_pump,
+            window,
+            context,
+            full_screen: false,
+            full_screen_monitor,
+            windowed_size: (width, height),
+            windowed_position: None,
+            window_focused: true,
+            game_controller_subsystem,
+            game_controllers: vec![],
+        };
+
+        if full_screen {
+            sdl2_window.apply_full_screen(true);
+        }
+
+        sdl2_window
+    }
+
+    fn update_input<T: InputUpdater>(&mut self, update: &mut T, ui: &mut Ui, current_time: &TimeMilliseconds) -> bool {
+        let mut event_update = false;
+        let (window_width, window_height) = self.window.borrow().size();
+
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+
+        for event in events {
+            match event {
+                Event::Quit { .. } => {
+                    update.set_quit(true);
+                    event_update = true;
+                }
+                Event::KeyDown { keycode: Some(keycode), repeat: false, .. } => {
+                    if update.key_input(convert_key(keycode), true, current_time) {
+                        event_update = true;
+                    }
+                }
+                Event::KeyUp { keycode: Some(keycode), .. } => {
+                    if update.key_input(convert_key(keycode), false, current_time) {
+                        event_update = true;
+                    }
+                }
+                Event::Window { win_event: WindowEvent::FocusGained, .. } => self.window_focused = true,
+                Event::Window { win_event: WindowEvent::FocusLost, .. } => self.window_focused = false,
+                Event::ControllerDeviceAdded { which, .. } => self.open_game_controller(which),
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.game_controllers.retain(|controller| controller.instance_id() != which);
+                }
+                Event::ControllerButtonDown { which, .. } |
+                Event::ControllerButtonUp { which, .. } |
+                Event::ControllerAxisMotion { which, .. } => {
+                    let controller_name = self.game_controllers
+                        .iter()
+                        .find(|controller| controller.instance_id() == which)
+                        .map(|controller| controller.name())
+                        .unwrap_or_default();
+
+                    if self.window_focused && handle_game_controller_input(update, &controller_name, &event, current_time) {
+                        event_update = true;
+                    }
+                }
+                _ => (),
+            }
+
+            if let Some(conrod_event) = convert_event(event, window_width, window_height) {
+                ui.handle_event(conrod_event);
+                event_update = true;
+            }
+        }
+
+        event_update
+    }
+
+    fn full_screen(&self) -> bool {
+        self.full_screen
+    }
+
+    fn set_full_screen(&mut self, value: bool) {
+        if self.full_screen == value {
+            return;
+        }
+
+        if !self.full_screen {
+            let window = self.window.borrow();
+            self.windowed_size = window.size();
+            self.windowed_position = Some(window.position());
+        }
+
+        self.apply_full_screen(value);
+    }
+
+    fn monitor_names(&self) -> Vec<String> {
+        let window = self.window.borrow();
+        let video_subsystem = window.subsystem();
+        let display_count = video_subsystem.num_video_displays().unwrap_or(0);
+
+        (0..display_count)
+            .map(|i| {
+                let name = video_subsystem.display_name(i).unwrap_or_else(|_| format!("Monitor {}", i + 1));
+
+                match video_subsystem.display_bounds(i) {
+                    Ok(bounds) => format!("{} ({}x{})", name, bounds.width(), bounds.height()),
+                    Err(_) => name,
+                }
+            })
+            .collect()
+    }
+
+    fn full_screen_monitor(&self) -> Option<usize> {
+        self.full_screen_monitor
+    }
+
+    fn set_full_screen_monitor(&mut self, monitor: Option<usize>) {
+        if self.full_screen_monitor == monitor {
+            return;
+        }
+
+        self.full_screen_monitor = monitor;
+
+        if self.full_screen {
+            // Move the window to the new display.
+            self.apply_full_screen(false);
+            self.apply_full_screen(true);
+        }
+    }
+
+    fn width_and_height(&self) -> (u32, u32) {
+        self.context.get_framebuffer_dimensions()
+    }
+
+    fn dpi_factor(&self) -> Scalar {
+        let window = self.window.borrow();
+        let (width, _) = window.size();
+        let (drawable_width, _) = window.drawable_size();
+
+        if width == 0 {
+            1.0
+        } else {
+            drawable_width as Scalar / width as Scalar
+        }
+    }
+}
+
+impl OpenGLWindow for SDL2Window {
+    fn draw(&mut self) -> Frame {
+        Frame::new(self.context.clone(), self.context.get_framebuffer_dimensions())
+    }
+
+    fn opengl_version(&self) -> &glium::Version {
+        self.context.get_opengl_version()
+    }
+}
+
+impl Facade for SDL2Window {
+    fn get_context(&self) -> &Rc<Context> {
+        &self.context
+    }
+}
+
+/// Glium backend for SDL2 window's OpenGL context.
+struct SDL2Backend {
+    window: Rc<RefCell<video::Window>>,
+    gl_context: GLContext,
+}
+
+unsafe impl Backend for SDL2Backend {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        self.window.borrow().gl_swap_window();
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        self.window.borrow().subsystem().gl_get_proc_address(symbol) as *const c_void
+    }
+
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        self.window.borrow().drawable_size()
+    }
+
+    fn is_current(&self) -> bool {
+        self.gl_context.is_current()
+    }
+
+    unsafe fn make_current(&self) {
+        self.window.borrow().gl_make_current(&self.gl_context).expect("OpenGL context make current error");
+    }
+}
+
+/// Converts SDL2 event to conrod input event.
+///
+/// Conrod uses coordinates where origin is at the center of the window
+/// and y axis points up.
+fn convert_event(event: Event, window_width: u32, window_height: u32) -> Option<Input> {
+    let tx = |x: i32| x as Scalar - window_width as Scalar / 2.0;
+    let ty = |y: i32| -(y as Scalar - window_height as Scalar / 2.0);
+
+    let input = match event {
+        Event::KeyDown { keycode: Some(keycode), .. } => Input::Press(input::Button::Keyboard(convert_key(keycode))),
+        Event::KeyUp { keycode: Some(keycode), .. } => Input::Release(input::Button::Keyboard(convert_key(keycode))),
+        Event::TextInput { text, .. } => Input::Text(text),
+        Event::MouseMotion { x, y, .. } => Input::Motion(Motion::MouseCursor { x: tx(x), y: ty(y) }),
+        Event::MouseWheel { x, y, .. } => Input::Motion(Motion::Scroll { x: x as Scalar, y: y as Scalar }),
+        Event::MouseButtonDown { mouse_btn, .. } => Input::Press(input::Button::Mouse(convert_mouse_button(mouse_btn))),
+        Event::MouseButtonUp { mouse_btn, .. } => Input::Release(input::Button::Mouse(convert_mouse_button(mouse_btn))),
+        Event::Window { win_event: WindowEvent::SizeChanged(width, height), .. } => Input::Resize(width as u32, height as u32),
+        Event::Window { win_event: WindowEvent::FocusGained, .. } => Input::Focus(true),
+        Event::Window { win_event: WindowEvent::FocusLost, .. } => Input::Focus(false),
+        _ => return None,
+    };
+
+    Some(input)
+}
+
+/// Conrod's key codes have the same values as SDL2's key codes.
+fn convert_key(keycode: Keycode) -> Key {
+    Key::from(keycode as i32 as u32)
+}
+
+fn convert_mouse_button(mouse_button: MouseButton) -> input::MouseButton {
+    match mouse_button {
+        MouseButton::Left => input::MouseButton::Left,
+        MouseButton::Middle => input::MouseButton::Middle,
+        MouseButton::Right => input::MouseButton::Right,
+        MouseButton::X1 => input::MouseButton::X1,
+        MouseButton::X2 => input::MouseButton::X2,
+        MouseButton::Unknown => input::MouseButton::Unknown,
+    }
+}
+
+/// Returns true if InputUpdater was updated.
+fn handle_game_controller_input<T: InputUpdater>(update: &mut T, controller_name: &str, event: &Event, current_time: &TimeMilliseconds) -> bool {
+    match *event {
+        Event::ControllerButtonDown { button, .. } => update.game_controller_button(controller_name, button_name(button), true, current_time),
+        Event::ControllerButtonUp { button, .. } => update.game_controller_button(controller_name, button_name(button), false, current_time),
+        Event::ControllerAxisMotion { which, axis, value, .. } => {
+            let value = value as f32 / i16::max_value() as f32;
+
+            // SDL2's y axis points down.
+            let (axis_name, value) = match axis {
+                Axis::LeftX => ("LeftStickX", value),
+                Axis::LeftY => ("LeftStickY", -value),
+                Axis::RightX => ("RightStickX", value),
+                Axis::RightY => ("RightStickY", -value),
+                Axis::TriggerLeft => ("LeftTrigger2", value),
+                Axis::TriggerRight => ("RightTrigger2", value),
+            };
+
+            update.game_controller_axis(which as usize, controller_name, axis_name, value, current_time)
+        }
+        _ => false,
+    }
+}
+
+/// Converts SDL2 button to gilrs button name.
+fn button_name(button: Button) -> &'static str {
+    match button {
+        Button::A => "South",
+        Button::B => "East",
+        Button::X => "West",
+        Button::Y => "North",
+        Button::Back => "Select",
+        Button::Guide => "Mode",
+        Button::Start => "Start",
+        Button::LeftStick => "LeftThumb",
+        Button::RightStick => "RightThumb",
+        Button::LeftShoulder => "LeftTrigger",
+        Button::RightShoulder => "RightTrigger",
+        Button::DPadUp => "DPadUp",
+        Button::DPadDown => "DPadDown",
+        Button::DPadLeft => "DPadLeft",
+        Button::DPadRight => "DPadRight",
+    }
+}